@@ -0,0 +1,102 @@
+// Builds the name table (see `src/name_table.rs` for its layout) from
+// data/name_probability.tsv, so that the program can look up names without parsing text. The
+// table is deflated to keep the program small.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const NAMES_PATH: &str = "data/name_probability.tsv";
+// Must match `src/name_table.rs`
+const BLOCK_LEN: usize = 16;
+
+fn main() {
+    println!("cargo:rerun-if-changed={NAMES_PATH}");
+    println!("cargo:rerun-if-changed=build.rs");
+    let text = fs::read_to_string(NAMES_PATH).expect("can't read the name table");
+
+    // Sorted by name; a repeated name keeps its last probability
+    let mut name_to_prob = BTreeMap::new();
+    for line in text.lines().skip(1).filter(|line| !line.trim().is_empty()) {
+        let (name, prob) = line
+            .split_once(['\t', ','])
+            .unwrap_or_else(|| panic!("expected 'name, tab, prob', not {line:?}"));
+        let prob = prob
+            .trim()
+            .parse::<f32>()
+            .unwrap_or_else(|_| panic!("bad probability in {line:?}"));
+        assert!(prob > 0.0 && prob <= 1.0, "bad probability in {line:?}");
+        name_to_prob.insert(canonical(name), prob);
+    }
+    assert!(!name_to_prob.is_empty(), "the name table is empty");
+
+    // Probabilities are stored as 16-bit steps between the smallest and largest log-probability
+    let min_prob = name_to_prob.values().copied().fold(1.0f32, f32::min);
+    let max_prob = name_to_prob.values().copied().fold(0.0f32, f32::max);
+    let min_ln = min_prob.ln();
+    let ln_step = (max_prob.ln() - min_ln) / u16::MAX as f32;
+
+    let mut entries = Vec::new();
+    let mut code_list = Vec::new();
+    let mut block_offset_list = Vec::new();
+    let mut previous: &[u8] = &[];
+    for (index, (name, prob)) in name_to_prob.iter().enumerate() {
+        let name = name.as_bytes();
+        let shared = if index % BLOCK_LEN == 0 {
+            block_offset_list.push(entries.len() as u32);
+            0
+        } else {
+            previous
+                .iter()
+                .zip(name)
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+        let suffix = &name[shared..];
+        // Both lengths in one byte when they fit, else 0xFF and a byte for each
+        if shared < 15 && suffix.len() < 16 {
+            entries.push(((shared as u8) << 4) | suffix.len() as u8);
+        } else {
+            entries.extend_from_slice(&[0xFF, shared as u8, suffix.len() as u8]);
+        }
+        entries.extend_from_slice(suffix);
+        let code = if ln_step > 0.0 {
+            ((prob.ln() - min_ln) / ln_step).round() as u16
+        } else {
+            0
+        };
+        code_list.extend_from_slice(&code.to_le_bytes());
+        previous = name;
+    }
+
+    let mut table = Vec::new();
+    table.extend_from_slice(&(name_to_prob.len() as u32).to_le_bytes());
+    table.extend_from_slice(&(block_offset_list.len() as u32).to_le_bytes());
+    table.extend_from_slice(&min_prob.to_le_bytes());
+    table.extend_from_slice(&min_ln.to_le_bytes());
+    table.extend_from_slice(&ln_step.to_le_bytes());
+    for block_offset in block_offset_list {
+        table.extend_from_slice(&block_offset.to_le_bytes());
+    }
+    table.extend_from_slice(&code_list);
+    table.extend_from_slice(&entries);
+
+    let deflated = miniz_oxide::deflate::compress_to_vec(&table, 10);
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("name_table.deflate"), deflated).unwrap();
+}
+
+// As `Token::to_canonical` makes it, for the ASCII names the table holds
+fn canonical(name: &str) -> String {
+    let name = name.trim().to_ascii_uppercase().replace(['.', '\''], "");
+    assert!(
+        !name.is_empty() && name.len() <= u8::MAX as usize,
+        "bad name length: {name:?}"
+    );
+    assert!(
+        name.bytes().all(|byte| byte.is_ascii_uppercase()),
+        "names in {NAMES_PATH} must be A-Z (with . and ' ignored), not {name:?}"
+    );
+    name
+}
@@ -0,0 +1,60 @@
+// Results downloaded as CSV (RFC 4180): fields may be quoted, so that "Smith, John" is one
+// field, a quote inside quotes is doubled, and a quoted field may even span lines. Each record
+// becomes one tab-separated result line, keeping every field in its place, so the header row
+// (when there is one) tells the matcher which fields are names, hometowns, ages, and times
+// (see `layout`).
+
+use crate::layout::rows_to_lines;
+
+pub(crate) fn parse_records(text: &str) -> Result<Vec<Vec<String>>, anyhow::Error> {
+    let mut record_list = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    // Where the open quote was, for the error message
+    let mut quote_line_number = 0;
+    let mut line_number = 1;
+    let mut chars = text.trim_start_matches('\u{FEFF}').chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line_number += 1;
+        }
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_quotes = true;
+                quote_line_number = line_number;
+            }
+            ',' => record.push(core::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\r' | '\n' => {
+                record.push(core::mem::take(&mut field));
+                record_list.push(core::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        anyhow::bail!("CSV quote opened on line {quote_line_number} is never closed");
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        record_list.push(record);
+    }
+    Ok(record_list)
+}
+
+// One tab-separated line per record (see `layout::rows_to_lines`)
+pub fn to_result_lines(text: &str) -> Result<Vec<String>, anyhow::Error> {
+    Ok(rows_to_lines(&parse_records(text)?))
+}
@@ -0,0 +1,209 @@
+// Text files in whatever encoding they were saved in. Rosters and results exported on Windows
+// are often Windows-1252 (a superset of Latin-1) or UTF-16 with a byte order mark, where reading
+// them as UTF-8 fails on the first accented letter. A byte order mark settles the encoding.
+// Without one, text with a zero in every other byte is UTF-16, valid UTF-8 is UTF-8, and anything
+// else is read as Windows-1252, which gives every byte some character.
+
+use std::fmt;
+use std::io::{self, BufRead};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Windows1252 => "Windows-1252",
+        })
+    }
+}
+
+// How many leading bytes to look at for UTF-16 without a byte order mark
+const SNIFF_LEN: usize = 1000;
+
+// Bytes 0x80 to 0x9F in Windows-1252. The five it leaves undefined keep their Latin-1 meaning.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+pub fn detect(bytes: &[u8]) -> Encoding {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return Encoding::Utf8;
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return Encoding::Utf16Le;
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return Encoding::Utf16Be;
+    }
+    // ASCII text as UTF-16 has a zero high byte in (nearly) every pair
+    let pair_list = bytes[..bytes.len().min(SNIFF_LEN) & !1].chunks(2);
+    let pair_count = pair_list.len();
+    if pair_count > 0 {
+        let (even_zeros, odd_zeros) = pair_list.fold((0, 0), |(even, odd), pair| {
+            (
+                even + (pair[0] == 0) as usize,
+                odd + (pair[1] == 0) as usize,
+            )
+        });
+        if odd_zeros * 10 >= pair_count * 8 && even_zeros * 10 < pair_count {
+            return Encoding::Utf16Le;
+        }
+        if even_zeros * 10 >= pair_count * 8 && odd_zeros * 10 < pair_count {
+            return Encoding::Utf16Be;
+        }
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        Encoding::Utf8
+    } else {
+        Encoding::Windows1252
+    }
+}
+
+// The text, without any byte order mark, and the encoding it was in
+pub fn decode(bytes: &[u8]) -> (String, Encoding) {
+    let encoding = detect(bytes);
+    (decode_as(bytes, encoding), encoding)
+}
+
+fn decode_as(bytes: &[u8], encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Utf8 => {
+            String::from_utf8_lossy(bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes))
+                .into_owned()
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let unit_list = bytes
+                .chunks_exact(2)
+                .map(|pair| match encoding {
+                    Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]]),
+                })
+                .collect::<Vec<_>>();
+            let unit_list = unit_list.strip_prefix(&[0xFEFF]).unwrap_or(&unit_list);
+            String::from_utf16_lossy(unit_list)
+        }
+        Encoding::Windows1252 => bytes
+            .iter()
+            .map(|byte| match byte {
+                0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+                _ => *byte as char,
+            })
+            .collect(),
+    }
+}
+
+// Lines read one at a time, without holding the whole file. UTF-16 is detected from the first
+// bytes. Otherwise each line is UTF-8 if it can be, and Windows-1252 if not.
+pub struct DecodedLines<R: BufRead> {
+    reader: R,
+    encoding: Option<Encoding>,
+}
+
+pub fn decode_lines<R: BufRead>(reader: R) -> DecodedLines<R> {
+    DecodedLines {
+        reader,
+        encoding: None,
+    }
+}
+
+impl<R: BufRead> DecodedLines<R> {
+    // UTF-16, or Windows-1252 once any line has needed it, or else UTF-8 (None before reading)
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+
+    fn detect_start(&mut self) -> io::Result<Encoding> {
+        let buffer = self.reader.fill_buf()?;
+        let encoding = match detect(&buffer[..buffer.len().min(SNIFF_LEN)]) {
+            // A line that isn't UTF-8 will say so
+            Encoding::Windows1252 => Encoding::Utf8,
+            encoding => encoding,
+        };
+        let bom_len = match encoding {
+            Encoding::Utf8 if buffer.starts_with(&[0xEF, 0xBB, 0xBF]) => 3,
+            Encoding::Utf16Le if buffer.starts_with(&[0xFF, 0xFE]) => 2,
+            Encoding::Utf16Be if buffer.starts_with(&[0xFE, 0xFF]) => 2,
+            _ => 0,
+        };
+        self.reader.consume(bom_len);
+        Ok(encoding)
+    }
+
+    // The bytes up to and including the next newline, which in UTF-16 is two bytes
+    fn read_line_bytes(&mut self, encoding: Encoding) -> io::Result<Vec<u8>> {
+        let mut line = Vec::new();
+        loop {
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(line);
+            }
+            match encoding {
+                // "\n" is 0A 00, so an 0A ending an odd length may start it
+                Encoding::Utf16Le => {
+                    if line.len() % 2 == 1 {
+                        let mut next = [0];
+                        if self.reader.read(&mut next)? == 0 {
+                            return Ok(line);
+                        }
+                        line.push(next[0]);
+                        if next[0] == 0 {
+                            return Ok(line);
+                        }
+                    }
+                }
+                // "\n" is 00 0A
+                Encoding::Utf16Be => {
+                    if line.len() % 2 == 0 && line[line.len() - 2] == 0 {
+                        return Ok(line);
+                    }
+                }
+                _ => return Ok(line),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for DecodedLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None => match self.detect_start() {
+                Ok(encoding) => *self.encoding.insert(encoding),
+                Err(e) => return Some(Err(e)),
+            },
+        };
+        let bytes = match self.read_line_bytes(encoding) {
+            Ok(bytes) if bytes.is_empty() => return None,
+            Ok(bytes) => bytes,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut line = match encoding {
+            Encoding::Utf8 | Encoding::Windows1252 => match String::from_utf8(bytes) {
+                Ok(line) => line,
+                Err(e) => {
+                    self.encoding = Some(Encoding::Windows1252);
+                    decode_as(e.as_bytes(), Encoding::Windows1252)
+                }
+            },
+            _ => decode_as(&bytes, encoding),
+        };
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Some(Ok(line))
+    }
+}
@@ -0,0 +1,351 @@
+// Measure how well the matcher does on labelled cases.
+//
+// A cases directory holds one sub-directory per case. Each case contains:
+//   members.txt -- First,Last,City lines, the same as `Config::find_matches` takes
+//   results.txt -- the race results, one result per line
+//   labels.tsv  -- one "member line<tab>result line" per true match (1-based line numbers)
+// Blank lines and lines starting with '#' in labels.tsv are ignored.
+
+use core::fmt;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use itertools::Itertools;
+
+use crate::{read_all_lines, Config, IncludeCity};
+
+pub const DEFAULT_THRESHOLDS: [f32; 7] = [0.01, 0.05, 0.1, 0.25, 0.5, 0.75, 0.9];
+const CALIBRATION_BIN_COUNT: usize = 10;
+const WORST_ERROR_COUNT: usize = 10;
+
+pub struct Case {
+    pub name: String,
+    pub member_lines: Vec<String>,
+    pub result_lines: Vec<String>,
+    // (member index, result line index), both 0-based
+    pub labels: HashSet<(usize, usize)>,
+}
+
+impl Case {
+    pub fn load(dir: &Path) -> Result<Case, anyhow::Error> {
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| dir.display().to_string());
+        let member_lines = read_all_lines(dir.join("members.txt"))?;
+        let result_lines = read_all_lines(dir.join("results.txt"))?;
+        let labels = parse_labels(&read_all_lines(dir.join("labels.tsv"))?)
+            .with_context(|| format!("Case '{name}'"))?;
+        Ok(Case {
+            name,
+            member_lines,
+            result_lines,
+            labels,
+        })
+    }
+}
+
+pub fn parse_labels(label_lines: &[String]) -> Result<HashSet<(usize, usize)>, anyhow::Error> {
+    let mut labels = HashSet::new();
+    for line in label_lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((member, result)) = line.split('\t').collect_tuple() else {
+            anyhow::bail!("Label line should be 'member line<tab>result line', not '{line}'");
+        };
+        let member = member.trim().parse::<usize>()?;
+        let result = result.trim().parse::<usize>()?;
+        if member == 0 || result == 0 {
+            anyhow::bail!("Label line numbers start at 1, not '{line}'");
+        }
+        labels.insert((member - 1, result - 1));
+    }
+    Ok(labels)
+}
+
+// Cases are sub-directories, visited in name order
+pub fn load_cases(dir: &Path) -> Result<Vec<Case>, anyhow::Error> {
+    let mut case_dirs = fs::read_dir(dir)
+        .with_context(|| format!("Reading '{}'", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    case_dirs.retain(|path| path.is_dir());
+    case_dirs.sort();
+    case_dirs.iter().map(|path| Case::load(path)).collect()
+}
+
+// One (member, result line) pair, either reported by the matcher or labelled as a match.
+#[derive(Clone, Debug)]
+pub struct Prediction {
+    pub case: String,
+    pub member_index: usize,
+    pub line_index: usize,
+    pub member_line: String,
+    pub result_line: String,
+    // 0.0 when the matcher didn't report the pair
+    pub prob: f32,
+    pub is_match: bool,
+}
+
+impl Prediction {
+    fn error(&self) -> f32 {
+        if self.is_match {
+            1.0 - self.prob
+        } else {
+            self.prob
+        }
+    }
+}
+
+// Runs the same code as `Config::find_matches`, but keeps the probabilities.
+pub fn predict(
+    config: &Config,
+    case: &Case,
+    include_city: IncludeCity,
+) -> Result<Vec<Prediction>, anyhow::Error> {
+    let (_prob_member_in_race, line_people_list) =
+        config.find_line_people(&case.member_lines, &case.result_lines, include_city)?;
+
+    let mut prediction_list = Vec::new();
+    let mut reported = HashSet::new();
+    for line_people in line_people_list.iter() {
+        for person_prob in line_people.person_prob_list.iter() {
+            let key = (person_prob.person.id, line_people.line_index);
+            reported.insert(key);
+            prediction_list.push(case.prediction(key, person_prob.prob));
+        }
+    }
+    for key in case.labels.iter().sorted() {
+        if !reported.contains(key) {
+            prediction_list.push(case.prediction(*key, 0.0));
+        }
+    }
+    Ok(prediction_list)
+}
+
+impl Case {
+    // Every (member, result) pair, counting only the members indexed and results that aren't
+    // header rows or blank
+    fn pair_count(&self, config: &Config) -> Result<usize, anyhow::Error> {
+        let member_count = config.index_members(&self.member_lines)?.len();
+        Ok(member_count * config.result_record_count(&self.result_lines))
+    }
+
+    fn prediction(&self, (member_index, line_index): (usize, usize), prob: f32) -> Prediction {
+        Prediction {
+            case: self.name.clone(),
+            member_index,
+            line_index,
+            member_line: self
+                .member_lines
+                .get(member_index)
+                .cloned()
+                .unwrap_or_default(),
+            result_line: self
+                .result_lines
+                .get(line_index)
+                .cloned()
+                .unwrap_or_default(),
+            prob,
+            is_match: self.labels.contains(&(member_index, line_index)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ThresholdStats {
+    pub threshold: f32,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+}
+
+impl ThresholdStats {
+    pub fn new(prediction_list: &[Prediction], threshold: f32) -> Self {
+        let mut stats = ThresholdStats {
+            threshold,
+            true_positives: 0,
+            false_positives: 0,
+            false_negatives: 0,
+        };
+        for prediction in prediction_list {
+            match (prediction.prob >= threshold, prediction.is_match) {
+                (true, true) => stats.true_positives += 1,
+                (true, false) => stats.false_positives += 1,
+                (false, true) => stats.false_negatives += 1,
+                (false, false) => {}
+            }
+        }
+        stats
+    }
+
+    pub fn precision(&self) -> f32 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    pub fn recall(&self) -> f32 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+
+    pub fn f1(&self) -> f32 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f32 / denominator as f32
+    }
+}
+
+// Reported pairs grouped by predicted probability
+#[derive(Clone, Debug)]
+pub struct CalibrationBin {
+    pub low: f32,
+    pub high: f32,
+    pub count: usize,
+    pub mean_prob: f32,
+    pub actual_rate: f32,
+}
+
+pub fn calibration(prediction_list: &[Prediction], bin_count: usize) -> Vec<CalibrationBin> {
+    let mut bin_list = (0..bin_count)
+        .map(|index| CalibrationBin {
+            low: index as f32 / bin_count as f32,
+            high: (index + 1) as f32 / bin_count as f32,
+            count: 0,
+            mean_prob: 0.0,
+            actual_rate: 0.0,
+        })
+        .collect_vec();
+    for prediction in prediction_list.iter().filter(|p| p.prob > 0.0) {
+        let index = ((prediction.prob * bin_count as f32) as usize).min(bin_count - 1);
+        let bin = &mut bin_list[index];
+        bin.count += 1;
+        bin.mean_prob += prediction.prob;
+        bin.actual_rate += if prediction.is_match { 1.0 } else { 0.0 };
+    }
+    for bin in bin_list.iter_mut().filter(|bin| bin.count > 0) {
+        bin.mean_prob /= bin.count as f32;
+        bin.actual_rate /= bin.count as f32;
+    }
+    bin_list
+}
+
+pub struct Report {
+    pub case_count: usize,
+    // every (member, result) pair across all cases (see `Case::pair_count`)
+    pub pair_count: usize,
+    pub prediction_list: Vec<Prediction>,
+    pub threshold_stats_list: Vec<ThresholdStats>,
+    pub calibration: Vec<CalibrationBin>,
+    pub worst_errors: Vec<Prediction>,
+}
+
+pub fn evaluate(
+    config: &Config,
+    case_list: &[Case],
+    include_city: IncludeCity,
+    threshold_list: &[f32],
+) -> Result<Report, anyhow::Error> {
+    let mut prediction_list = Vec::new();
+    let mut pair_count = 0;
+    for case in case_list {
+        prediction_list.extend(predict(config, case, include_city)?);
+        pair_count += case.pair_count(config)?;
+    }
+
+    let threshold_stats_list = threshold_list
+        .iter()
+        .map(|threshold| ThresholdStats::new(&prediction_list, *threshold))
+        .collect_vec();
+    let calibration = calibration(&prediction_list, CALIBRATION_BIN_COUNT);
+    let worst_errors = prediction_list
+        .iter()
+        .filter(|prediction| prediction.error() > 0.0)
+        .sorted_by(|a, b| b.error().partial_cmp(&a.error()).unwrap())
+        .take(WORST_ERROR_COUNT)
+        .cloned()
+        .collect_vec();
+
+    Ok(Report {
+        case_count: case_list.len(),
+        pair_count,
+        prediction_list,
+        threshold_stats_list,
+        calibration,
+        worst_errors,
+    })
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labelled_count = self.prediction_list.iter().filter(|p| p.is_match).count();
+        writeln!(
+            f,
+            "{} cases, {} labelled matches",
+            self.case_count, labelled_count
+        )?;
+
+        writeln!(f, "\nthreshold\tTP\tFP\tFN\tprecision\trecall\tF1")?;
+        for stats in self.threshold_stats_list.iter() {
+            writeln!(
+                f,
+                "{:.2}\t{}\t{}\t{}\t{:.3}\t{:.3}\t{:.3}",
+                stats.threshold,
+                stats.true_positives,
+                stats.false_positives,
+                stats.false_negatives,
+                stats.precision(),
+                stats.recall(),
+                stats.f1()
+            )?;
+        }
+
+        writeln!(f, "\npredicted\tcount\tmean predicted\tactual")?;
+        for bin in self.calibration.iter() {
+            writeln!(
+                f,
+                "{:.1}-{:.1}\t{}\t{:.3}\t{:.3}",
+                bin.low, bin.high, bin.count, bin.mean_prob, bin.actual_rate
+            )?;
+        }
+
+        writeln!(f, "\nworst errors")?;
+        for prediction in self.worst_errors.iter() {
+            writeln!(
+                f,
+                "{}\t{}\t{:.3}\tmember {}: {}\tresult {}: {}",
+                prediction.case,
+                if prediction.is_match {
+                    "missed"
+                } else {
+                    "wrong"
+                },
+                prediction.prob,
+                prediction.member_index + 1,
+                prediction.member_line,
+                prediction.line_index + 1,
+                prediction.result_line
+            )?;
+        }
+        Ok(())
+    }
+}
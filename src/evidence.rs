@@ -0,0 +1,318 @@
+// Evidence from structured fields (see `fields`), scored next to the name and city points.
+//
+// Each field is one independent naive-Bayes term: how likely the line's value agrees with the
+// member if the line is theirs (`prob_right`) versus by coincidence. The coincidence comes from
+// the race itself, so a race full of 40-year-olds makes an age of 40 weak evidence. A field
+// that is missing from the line or from the member adds nothing.
+//
+// A bib from the members file (say, from a group registration) that agrees is nearly certain,
+// but names are still scored so that a bib typed on the wrong line gets flagged.
+//
+// A member's gender may itself be uncertain (guessed from their first name), so gender is
+// scored as P(line's gender | member) / P(line's gender in this race).
+
+use crate::fields::{Gender, ResultFields};
+use crate::members::normalize_bib;
+use crate::{delta_one, Annotation, Config, Person, Score};
+
+const MAX_AGE: usize = 120;
+
+// What one race's lines say, beyond their tokens
+pub(crate) struct RaceFields<'a> {
+    fields_list: &'a [ResultFields],
+    // Normalized, one per line
+    bib_list: Vec<Option<String>>,
+    field_counts: FieldCounts,
+}
+
+// Counts over the whole race, which can be added up a part of the race at a time (see `stream`)
+#[derive(Clone)]
+pub(crate) struct FieldCounts {
+    bib_count: usize,
+    age_model: Option<AgeModel>,
+    gender_model: GenderModel,
+}
+
+impl FieldCounts {
+    pub fn new(race_year: Option<u32>) -> Self {
+        Self {
+            bib_count: 0,
+            age_model: race_year.map(AgeModel::new),
+            gender_model: GenderModel::default(),
+        }
+    }
+
+    pub fn add(&mut self, fields_list: &[ResultFields]) {
+        self.bib_count += fields_list
+            .iter()
+            .filter(|fields| fields.bib.is_some())
+            .count();
+        if let Some(age_model) = &mut self.age_model {
+            age_model.add(fields_list);
+        }
+        self.gender_model.add(fields_list);
+    }
+}
+
+impl<'a> RaceFields<'a> {
+    pub fn new(fields_list: &'a [ResultFields], race_year: Option<u32>) -> Self {
+        let mut field_counts = FieldCounts::new(race_year);
+        field_counts.add(fields_list);
+        Self::with_counts(fields_list, field_counts)
+    }
+
+    // Lines from part of a race, scored against counts from all of it
+    pub fn with_counts(fields_list: &'a [ResultFields], field_counts: FieldCounts) -> Self {
+        Self {
+            fields_list,
+            bib_list: fields_list
+                .iter()
+                .map(|fields| fields.bib.as_deref().map(normalize_bib))
+                .collect(),
+            field_counts,
+        }
+    }
+
+    pub fn bib(&self, line_index: usize) -> Option<&String> {
+        self.bib_list[line_index].as_ref()
+    }
+
+    fn bib_agrees(&self, person: &Person, line_index: usize) -> Option<bool> {
+        Some(person.bib.as_ref()? == self.bib(line_index)?)
+    }
+
+    // `name_delta` is the name points alone
+    pub fn flags(&self, person: &Person, line_index: usize, name_delta: f32) -> Vec<String> {
+        match self.bib_agrees(person, line_index) {
+            Some(true) if name_delta < 0.0 => vec!["bib matches, but name doesn't".to_string()],
+            Some(false) if name_delta > 0.0 => vec![format!(
+                "name matches, but bib is {}, not {}",
+                self.bib(line_index).unwrap(),
+                person.bib.as_ref().unwrap()
+            )],
+            _ => vec![],
+        }
+    }
+
+    pub fn scores(
+        &self,
+        config: &Config,
+        person: &Person,
+        line_index: usize,
+    ) -> Vec<Box<dyn Score>> {
+        let fields = &self.fields_list[line_index];
+        let mut score_list: Vec<Box<dyn Score>> = Vec::new();
+        if let (Some(age_model), Some(birth_year), Some(age)) =
+            (&self.field_counts.age_model, person.birth_year, fields.age)
+        {
+            score_list.push(Box::new(age_model.score(
+                birth_year,
+                age,
+                config.prob_age_right,
+            )));
+        }
+        if let Some(agrees) = self.bib_agrees(person, line_index) {
+            // Bibs are unique within a race
+            let prob_coincidence = 1.0 / (self.field_counts.bib_count + 1) as f32;
+            score_list.push(Box::new(FieldScore::new(
+                "bib",
+                format!("bib {}", person.bib.as_ref().unwrap()),
+                format!("bib {}", self.bib(line_index).unwrap()),
+                agrees,
+                config.prob_bib_right,
+                prob_coincidence,
+            )));
+        }
+        if let (Some(prob_female), Some(gender)) = (person.prob_female, fields.gender) {
+            score_list.push(Box::new(self.field_counts.gender_model.score(
+                prob_female,
+                gender,
+                config.prob_gender_right,
+            )));
+        }
+        score_list
+    }
+}
+
+// Ages in the race, for the chance that a line's age agrees with a member's by coincidence
+#[derive(Clone)]
+struct AgeModel {
+    race_year: u32,
+    age_count_list: Vec<usize>,
+    total: usize,
+}
+
+impl AgeModel {
+    fn new(race_year: u32) -> Self {
+        Self {
+            race_year,
+            age_count_list: vec![0; MAX_AGE + 1],
+            total: 0,
+        }
+    }
+
+    fn add(&mut self, fields_list: &[ResultFields]) {
+        for age in fields_list.iter().filter_map(|fields| fields.age) {
+            self.age_count_list[(age as usize).min(MAX_AGE)] += 1;
+            self.total += 1;
+        }
+    }
+
+    // Depending on their birthday, a member is (race year - birth year) or one less, so allow
+    // one year either way.
+    fn score(&self, birth_year: u32, age: u32, prob_right: f32) -> FieldScore {
+        let expected = self.race_year.saturating_sub(birth_year) as usize;
+        let low = expected.saturating_sub(1).min(MAX_AGE);
+        let high = (expected + 1).min(MAX_AGE);
+        let in_window = self.age_count_list[low..=high].iter().sum::<usize>();
+        // Smoothed, so that an age no one else has still isn't certain
+        let prob_coincidence = (in_window + 1) as f32 / (self.total + 2) as f32;
+        let agrees = (age as usize).abs_diff(expected) <= 1;
+        FieldScore::new(
+            "age",
+            format!("born {birth_year}"),
+            format!("age {age}"),
+            agrees,
+            prob_right,
+            prob_coincidence,
+        )
+    }
+}
+
+// Genders in the race, for the chance that a line's gender agrees by coincidence
+#[derive(Clone, Default)]
+struct GenderModel {
+    female_count: usize,
+    total: usize,
+}
+
+impl GenderModel {
+    fn add(&mut self, fields_list: &[ResultFields]) {
+        for gender in fields_list.iter().filter_map(|fields| fields.gender) {
+            self.female_count += (gender == Gender::Female) as usize;
+            self.total += 1;
+        }
+    }
+
+    fn score(&self, prob_female: f32, gender: Gender, prob_right: f32) -> FieldScore {
+        let (prob_member, count) = match gender {
+            Gender::Female => (prob_female, self.female_count),
+            Gender::Male => (1.0 - prob_female, self.total - self.female_count),
+        };
+        let prob_line_given_member =
+            prob_member * prob_right + (1.0 - prob_member) * (1.0 - prob_right);
+        let prob_coincidence = (count + 1) as f32 / (self.total + 2) as f32;
+        let member_value = if prob_female == 1.0 {
+            "F".to_string()
+        } else if prob_female == 0.0 {
+            "M".to_string()
+        } else {
+            format!("first name {:.0}% F", prob_female * 100.0)
+        };
+        FieldScore::new(
+            "gender",
+            member_value,
+            gender.to_string(),
+            true, // the line's gender, whichever it is, was seen
+            prob_line_given_member,
+            prob_coincidence,
+        )
+    }
+}
+
+// Like `SingleScore`, most fields are only for `html`
+#[allow(dead_code)]
+#[derive(Debug)]
+pub(crate) struct FieldScore {
+    field: &'static str,
+    member_value: String,
+    line_value: String,
+    agrees: bool,
+    prob_right: f32,
+    prob_coincidence: f32,
+    delta: f32,
+}
+
+impl FieldScore {
+    fn new(
+        field: &'static str,
+        member_value: String,
+        line_value: String,
+        agrees: bool,
+        prob_right: f32,
+        prob_coincidence: f32,
+    ) -> Self {
+        Self {
+            field,
+            member_value,
+            line_value,
+            agrees,
+            prob_right,
+            prob_coincidence,
+            delta: delta_one(agrees, prob_coincidence, prob_right),
+        }
+    }
+}
+
+impl Score for FieldScore {
+    fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    fn title(&self) -> String {
+        self.field.to_string()
+    }
+
+    // Fields aren't tokens, so they show up as notes instead
+    fn annotations(&self) -> Vec<Annotation> {
+        vec![]
+    }
+
+    fn notes(&self) -> Vec<(String, f32)> {
+        vec![(
+            format!("{} ({})", self.line_value, self.member_value),
+            self.delta,
+        )]
+    }
+
+    fn html(&self) -> String {
+        format!(
+            "<table border=\"1\" style=\"border-collapse: collapse; margin-right: 20px;\">
+                    <tr>
+                        <th colspan=\"2\" style=\"text-align: center; font-weight: bold;\">{}</th>
+                    </tr>
+                    <tr>
+                        <td>member</td>
+                        <td>{}</td>
+                    </tr>
+                    <tr>
+                        <td>line</td>
+                        <td>{}</td>
+                    </tr>
+                    <tr>
+                        <td>agrees</td>
+                        <td>{}</td>
+                    </tr>
+                    <tr>
+                        <td>prob_right</td>
+                        <td>{}</td>
+                    </tr>
+                    <tr>
+                        <td>prob_coincid</td>
+                        <td>{}</td>
+                    </tr>
+                    <tr>
+                        <td>delta</td>
+                        <td>{}</td>
+                    </tr>
+                </table>",
+            self.field,
+            self.member_value,
+            self.line_value,
+            self.agrees,
+            self.prob_right,
+            self.prob_coincidence,
+            self.delta
+        )
+    }
+}
@@ -0,0 +1,284 @@
+// Pull structured fields (place, bib, age, gender, division, and times) out of result lines.
+// The matcher itself only sees tokens; these fields are for what happens after matching, like
+// reports and club scoring.
+//
+// Results often give gender in a section heading ("WOMEN", then later "MEN") rather than on
+// each line, so the lines are parsed in order and a heading applies until the next one.
+
+use core::fmt;
+
+use serde::Serialize;
+
+use crate::layout::{split_fields, Column, Layout};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum Gender {
+    Female,
+    Male,
+}
+
+impl Gender {
+    pub(crate) fn parse(word: &str) -> Option<Gender> {
+        match word.to_lowercase().as_str() {
+            "f" | "w" | "female" | "women" | "woman" | "women's" => Some(Gender::Female),
+            "m" | "male" | "men" | "man" | "men's" => Some(Gender::Male),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Gender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Gender::Female => write!(f, "F"),
+            Gender::Male => write!(f, "M"),
+        }
+    }
+}
+
+// Times are in seconds. Fields a line doesn't have are left out of the JSON.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ResultFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub place: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bib: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gender: Option<Gender>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub division: Option<String>,
+    // The finish time: chip time if known, else the only (or gun) time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chip_time: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gun_time: Option<f32>,
+}
+
+impl ResultFields {
+    fn is_result(&self) -> bool {
+        self.place.is_some() || self.time.is_some()
+    }
+}
+
+// One `ResultFields` per line (headings and blank lines get empty fields). A header row, when
+// there is one, says which column is which until the next header row (see `layout`).
+pub fn parse_result_fields(result_lines: &[String]) -> Vec<ResultFields> {
+    parse_result_fields_in(&Layout::detect(result_lines), result_lines)
+}
+
+pub(crate) fn parse_result_fields_in(
+    layout: &Layout,
+    result_lines: &[String],
+) -> Vec<ResultFields> {
+    let mut section_gender = None;
+    result_lines
+        .iter()
+        .enumerate()
+        .map(|(line_index, line)| {
+            if let Some(gender) = heading_gender(line) {
+                section_gender = Some(gender);
+                return ResultFields::default();
+            }
+            if layout.is_header(line_index) {
+                return ResultFields::default();
+            }
+            let mut fields = match layout.columns(line_index, line) {
+                Some(column_field_list) => ResultFields::parse_with_columns(&column_field_list),
+                None => ResultFields::parse_fields(&layout.fields(line_index, line)),
+            };
+            if fields.gender.is_none() && fields.is_result() {
+                fields.gender = section_gender;
+            }
+            fields
+        })
+        .collect()
+}
+
+impl ResultFields {
+    // Parses one line on its own, without any header row or section heading.
+    pub fn parse(line: &str) -> ResultFields {
+        Self::parse_fields(&split_fields(line))
+    }
+
+    fn parse_fields(field_list: &[&str]) -> ResultFields {
+        let field_list = field_list
+            .iter()
+            .copied()
+            .filter(|field| !field.is_empty())
+            .collect::<Vec<_>>();
+        let mut fields = ResultFields {
+            place: field_list.first().and_then(|field| parse_place(field)),
+            ..ResultFields::default()
+        };
+
+        // Numbers after the place: with two, the bib then the age (as in the sample results);
+        // with one, an age if it could be one.
+        let number_list = field_list
+            .iter()
+            .skip(usize::from(fields.place.is_some()))
+            .filter(|field| field.chars().all(|c| c.is_ascii_digit()))
+            .collect::<Vec<_>>();
+        match number_list.as_slice() {
+            [bib, age, ..] => {
+                fields.bib = Some(bib.to_string());
+                fields.age = parse_age(age);
+            }
+            [number] => match parse_age(number) {
+                Some(age) => fields.age = Some(age),
+                None => fields.bib = Some(number.to_string()),
+            },
+            [] => {}
+        }
+
+        // Without columns, a lone "M" or "F" may be a middle initial, so only a division counts
+        for field in field_list.iter() {
+            if let Some(gender) = division_gender(field) {
+                fields.gender = fields.gender.or(Some(gender));
+                fields.division = fields.division.or(Some(field.to_string()));
+            }
+        }
+
+        // Chip time is never more than gun time. Much shorter times are paces or splits.
+        let time_list = field_list
+            .iter()
+            .filter_map(|field| parse_time(field))
+            .collect::<Vec<_>>();
+        let longest = time_list.iter().copied().fold(0.0, f32::max);
+        let finish_list = time_list
+            .into_iter()
+            .filter(|time| *time >= longest / 2.0)
+            .collect::<Vec<_>>();
+        match finish_list.as_slice() {
+            [] => {}
+            [time] => fields.time = Some(*time),
+            _ => {
+                fields.chip_time = finish_list.iter().copied().reduce(f32::min);
+                fields.gun_time = Some(longest);
+                fields.time = fields.chip_time;
+            }
+        }
+        fields
+    }
+
+    fn parse_with_columns(column_field_list: &[(Option<Column>, &str)]) -> ResultFields {
+        let mut fields = ResultFields::default();
+        let mut official_time = None;
+        for (column, field) in column_field_list.iter() {
+            let Some(column) = column else {
+                continue;
+            };
+            if field.is_empty() {
+                continue;
+            }
+            match column {
+                Column::Place => fields.place = parse_place(field),
+                Column::Bib => fields.bib = Some(field.to_string()),
+                Column::Age => fields.age = parse_age(field),
+                Column::Gender => fields.gender = field_gender(field),
+                Column::Division => {
+                    fields.division = Some(field.to_string());
+                    fields.gender = fields.gender.or(field_gender(field));
+                }
+                Column::ChipTime => fields.chip_time = parse_time(field),
+                Column::GunTime => fields.gun_time = parse_time(field),
+                Column::Time => official_time = parse_time(field),
+                Column::Name | Column::Location => {}
+            }
+        }
+        fields.time = fields.chip_time.or(official_time).or(fields.gun_time);
+        fields
+    }
+}
+
+fn parse_place(field: &str) -> Option<usize> {
+    field.trim_end_matches('.').parse::<usize>().ok()
+}
+
+fn parse_age(field: &str) -> Option<u32> {
+    field
+        .parse::<u32>()
+        .ok()
+        .filter(|age| (5..=100).contains(age))
+}
+
+// A line of words (no digits) that names a gender, such as "WOMEN" or "Men's Results"
+pub(crate) fn heading_gender(line: &str) -> Option<Gender> {
+    if line.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let gender_list = line
+        .split(|c: char| !c.is_alphabetic() && c != '\'')
+        .filter(|word| word.len() > 1)
+        .filter_map(Gender::parse)
+        .collect::<Vec<_>>();
+    match gender_list.as_slice() {
+        [gender] => Some(*gender),
+        _ => None,
+    }
+}
+
+// A whole field of "F" or "Male", or a division such as "M35-39" or "F40+"
+fn field_gender(field: &str) -> Option<Gender> {
+    Gender::parse(field).or_else(|| division_gender(field))
+}
+
+// A division such as "M35-39", "F9", or "F40+", but not a bib such as "F123"
+fn division_gender(field: &str) -> Option<Gender> {
+    let mut chars = field.chars();
+    let gender = Gender::parse(&chars.next()?.to_string())?;
+    let is_age = |s: &str| (1..=2).contains(&s.len()) && s.chars().all(|c| c.is_ascii_digit());
+    let rest = chars.as_str();
+    let is_division = match rest.split_once('-') {
+        Some((low, high)) => is_age(low) && is_age(high),
+        None => match rest.strip_suffix('+') {
+            Some(low) => low.len() == 2 && is_age(low),
+            None => is_age(rest),
+        },
+    };
+    is_division.then_some(gender)
+}
+
+// "H:MM:SS", "MM:SS", or either with tenths, such as "2:29:35" or "16:02.4"
+pub fn parse_time(field: &str) -> Option<f32> {
+    let part_list = field.split(':').collect::<Vec<_>>();
+    if !(2..=3).contains(&part_list.len()) {
+        return None;
+    }
+    let (last, rest) = part_list.split_last()?;
+    let seconds = last.parse::<f32>().ok()?;
+    if last.split('.').next()?.len() != 2 || !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    let mut total = 0.0;
+    for (index, part) in rest.iter().enumerate() {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let value = part.parse::<f32>().ok()?;
+        if index > 0 && (part.len() != 2 || value >= 60.0) {
+            return None;
+        }
+        total = total * 60.0 + value;
+    }
+    Some(total * 60.0 + seconds)
+}
+
+// "2:29:35", or "16:02" when under an hour
+pub fn format_time(seconds: f32) -> String {
+    let all_tenths = (seconds * 10.0).round() as usize;
+    let (whole, tenths) = (all_tenths / 10, all_tenths % 10);
+    let (hours, minutes, secs) = (whole / 3600, whole / 60 % 60, whole % 60);
+    let mut text = if hours > 0 {
+        format!("{hours}:{minutes:02}:{secs:02}")
+    } else {
+        format!("{minutes}:{secs:02}")
+    };
+    if tenths > 0 {
+        text.push_str(&format!(".{tenths}"));
+    }
+    text
+}
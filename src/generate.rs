@@ -0,0 +1,376 @@
+// Make up races and rosters for testing and benchmarking, without using real people's data.
+//
+// Names are sampled (by frequency) from the same embedded name table the matcher uses, so
+// common names are common and rare names are rare. Club members are then hidden in the
+// results, sometimes under a nickname, with a typo, or without their city. The output is a
+// case directory that `evaluate` can read directly.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+use crate::name_table::NameTable;
+use crate::{extract_name_to_nicknames_set, Token};
+
+// Where club members live
+const CLUB_CITIES: [&str; 12] = [
+    "Bellevue",
+    "Redmond",
+    "Kirkland",
+    "Sammamish",
+    "Bothell",
+    "Woodinville",
+    "Issaquah",
+    "Mercer Island",
+    "Mill Creek",
+    "Duvall",
+    "Snoqualmie",
+    "Kenmore",
+];
+
+// Where everyone else lives (club cities show up, too)
+const OTHER_CITIES: [&str; 20] = [
+    "Seattle",
+    "Tacoma",
+    "Spokane",
+    "Portland",
+    "Eugene",
+    "Boise",
+    "Boulder",
+    "Denver",
+    "Oakland",
+    "San Diego",
+    "Flagstaff",
+    "Tucson",
+    "Chicago",
+    "Boston",
+    "New York",
+    "Lake Forest Park",
+    "North Bend",
+    "Walla Walla",
+    "Yakima",
+    "Olympia",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResultLayout {
+    // Place, Bib, Name, Age, Hometown, Finish -- like the sample results
+    Tab,
+    // Place,First,Last,City,Time
+    Csv,
+    // Space-aligned columns with "Last, First" names
+    FixedWidth,
+}
+
+impl FromStr for ResultLayout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tab" => Ok(ResultLayout::Tab),
+            "csv" => Ok(ResultLayout::Csv),
+            "fixed" | "fixed-width" => Ok(ResultLayout::FixedWidth),
+            _ => anyhow::bail!("Layout should be 'tab', 'csv', or 'fixed', not '{s}'"),
+        }
+    }
+}
+
+pub struct Generator {
+    pub seed: u64,
+    pub member_count: usize,
+    pub result_count: usize,
+    // How many of the members are hidden in the results
+    pub members_in_race: usize,
+    pub prob_nickname: f32,
+    pub prob_typo: f32,
+    pub prob_missing_city: f32,
+    pub layout: ResultLayout,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            member_count: 300,
+            result_count: 1000,
+            members_in_race: 30,
+            prob_nickname: 0.1,
+            prob_typo: 0.02,
+            prob_missing_city: 0.1,
+            layout: ResultLayout::Tab,
+        }
+    }
+}
+
+pub struct SyntheticRace {
+    pub member_lines: Vec<String>,
+    pub result_lines: Vec<String>,
+    // (member index, result line index), both 0-based
+    pub labels: Vec<(usize, usize)>,
+}
+
+impl SyntheticRace {
+    // Writes members.txt, results.txt, and labels.tsv (1-based) into `dir`.
+    pub fn write(&self, dir: &Path) -> Result<(), anyhow::Error> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("members.txt"), lines_to_text(&self.member_lines))?;
+        fs::write(dir.join("results.txt"), lines_to_text(&self.result_lines))?;
+        let label_lines = self
+            .labels
+            .iter()
+            .map(|(member_index, line_index)| format!("{}\t{}", member_index + 1, line_index + 1))
+            .collect_vec();
+        fs::write(dir.join("labels.tsv"), lines_to_text(&label_lines))?;
+        Ok(())
+    }
+}
+
+fn lines_to_text(lines: &[String]) -> String {
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+struct Runner {
+    first: String,
+    last: String,
+    city: String,
+}
+
+impl Generator {
+    pub fn generate(&self) -> Result<SyntheticRace, anyhow::Error> {
+        if self.members_in_race > self.member_count.min(self.result_count) {
+            anyhow::bail!(
+                "Can't put {} members into a race of {} with {} members",
+                self.members_in_race,
+                self.result_count,
+                self.member_count
+            );
+        }
+        let mut rng = Rng::new(self.seed);
+        let name_sampler = NameSampler::new();
+        let name_to_nickname_set = extract_name_to_nicknames_set();
+
+        let member_list = (0..self.member_count)
+            .map(|_| Runner {
+                first: name_sampler.sample(&mut rng),
+                last: name_sampler.sample(&mut rng),
+                city: rng.choose(&CLUB_CITIES).to_string(),
+            })
+            .collect_vec();
+
+        // Which places the members finished in
+        let mut place_to_member = HashMap::new();
+        let mut member_indexes = (0..self.member_count).collect_vec();
+        rng.shuffle(&mut member_indexes);
+        let mut places = (0..self.result_count).collect_vec();
+        rng.shuffle(&mut places);
+        for (member_index, place) in member_indexes
+            .iter()
+            .zip(places.iter())
+            .take(self.members_in_race)
+        {
+            place_to_member.insert(*place, *member_index);
+        }
+
+        let mut result_lines = vec![self.header()];
+        let mut labels = Vec::new();
+        for place in 0..self.result_count {
+            let runner = if let Some(member_index) = place_to_member.get(&place) {
+                labels.push((*member_index, result_lines.len()));
+                self.as_entered(&member_list[*member_index], &name_to_nickname_set, &mut rng)
+            } else {
+                let city = if rng.next_f32() < 0.2 {
+                    rng.choose(&CLUB_CITIES)
+                } else {
+                    rng.choose(&OTHER_CITIES)
+                };
+                Runner {
+                    first: name_sampler.sample(&mut rng),
+                    last: name_sampler.sample(&mut rng),
+                    city: city.to_string(),
+                }
+            };
+            result_lines.push(self.result_line(place + 1, &runner, &mut rng));
+        }
+        labels.sort();
+
+        let member_lines = member_list
+            .iter()
+            .map(|member| format!("{}\t{}\t{}", member.first, member.last, member.city))
+            .collect_vec();
+        Ok(SyntheticRace {
+            member_lines,
+            result_lines,
+            labels,
+        })
+    }
+
+    // How a member's name and city might look in the results
+    fn as_entered(
+        &self,
+        member: &Runner,
+        name_to_nickname_set: &HashMap<Token, HashSet<Token>>,
+        rng: &mut Rng,
+    ) -> Runner {
+        let mut first = member.first.clone();
+        if rng.next_f32() < self.prob_nickname {
+            if let Some(nickname_set) =
+                Token::find(&member.first).and_then(|first| name_to_nickname_set.get(&first))
+            {
+                let nickname_list = nickname_set.iter().map(|t| t.to_string()).sorted();
+                first = title_case(rng.choose(&nickname_list.collect_vec()).as_str());
+            }
+        }
+        let mut last = member.last.clone();
+        if rng.next_f32() < self.prob_typo {
+            last = typo(&last, rng);
+        }
+        let city = if rng.next_f32() < self.prob_missing_city {
+            String::new()
+        } else {
+            member.city.clone()
+        };
+        Runner { first, last, city }
+    }
+
+    fn header(&self) -> String {
+        match self.layout {
+            ResultLayout::Tab => "Place\tBib\tName\tAge\tHometown\tFinish".to_string(),
+            ResultLayout::Csv => "Place,First,Last,City,Time".to_string(),
+            ResultLayout::FixedWidth => {
+                format!(
+                    "{:<6}{:<30}{:<5}{:<20}{}",
+                    "Place", "Name", "Age", "City", "Time"
+                )
+            }
+        }
+    }
+
+    fn result_line(&self, place: usize, runner: &Runner, rng: &mut Rng) -> String {
+        // Finish times spread from about 16 to 60 minutes
+        let seconds = 16 * 60 + place * 44 * 60 / self.result_count.max(1) + rng.below(30);
+        let time = format!("{}:{:02}", seconds / 60, seconds % 60);
+        let age = 14 + rng.below(60);
+        match self.layout {
+            ResultLayout::Tab => format!(
+                "{place}\t{}\t{} {}\t{age}\t{}\t{time}",
+                1 + rng.below(self.result_count * 2),
+                runner.first,
+                runner.last,
+                runner.city,
+            ),
+            ResultLayout::Csv => format!(
+                "{place},{},{},{},{time}",
+                runner.first, runner.last, runner.city
+            ),
+            ResultLayout::FixedWidth => format!(
+                "{:<6}{:<30}{:<5}{:<20}{time}",
+                place,
+                format!("{}, {}", runner.last, runner.first),
+                age,
+                runner.city
+            ),
+        }
+    }
+}
+
+fn title_case(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+// Swap two neighboring letters or drop one
+fn typo(name: &str, rng: &mut Rng) -> String {
+    let mut chars = name.chars().collect_vec();
+    if chars.len() < 4 {
+        return name.to_string();
+    }
+    let index = 1 + rng.below(chars.len() - 2);
+    if rng.next_f32() < 0.5 {
+        chars.swap(index, index + 1);
+    } else {
+        chars.remove(index);
+    }
+    chars.into_iter().collect()
+}
+
+// Samples names in proportion to how often they appear in the name table
+struct NameSampler {
+    name_list: Vec<String>,
+    cumulative: Vec<f64>,
+}
+
+impl NameSampler {
+    fn new() -> Self {
+        let mut name_list = Vec::new();
+        let mut cumulative = Vec::new();
+        let mut total = 0.0f64;
+        for (name, prob) in NameTable::bundled().iter() {
+            total += prob as f64;
+            name_list.push(name);
+            cumulative.push(total);
+        }
+        Self {
+            name_list,
+            cumulative,
+        }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> String {
+        let total = self.cumulative.last().copied().unwrap_or(0.0);
+        let target = rng.next_f64() * total;
+        let index = self
+            .cumulative
+            .partition_point(|value| *value < target)
+            .min(self.name_list.len() - 1);
+        title_case(&self.name_list[index])
+    }
+}
+
+// SplitMix64: small, fast, and the same on every platform (including WASM)
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        self.next_f64() as f32
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
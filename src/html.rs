@@ -0,0 +1,152 @@
+// Results from a saved web page. Timing companies publish results as HTML tables, and pasting
+// them from a browser loses the columns. Instead, every <table> in the page is read (nothing is
+// fetched) and the results table is chosen: a table whose header row names known columns (see
+// `layout`) wins, and among the rest, the one with the most rows of three or more cells. Its rows
+// become tab-separated result lines, header row first.
+
+use itertools::Itertools;
+
+use crate::layout::{rows_to_lines, Column};
+use crate::markup::{self, Event};
+
+// Cells spanning more columns than this are cut short
+const MAX_COLSPAN: usize = 100;
+
+// A table's rows of cell text, with header cells (<th>) counted like any other
+pub(crate) type Table = Vec<Vec<String>>;
+
+// A table still being read: its rows so far, and the open row and cell (with its colspan)
+#[derive(Default)]
+struct OpenTable {
+    row_list: Table,
+    row: Option<Vec<String>>,
+    cell: Option<(String, usize)>,
+}
+
+impl OpenTable {
+    fn finish(mut self) -> Table {
+        finish_row(&mut self.row_list, self.row, self.cell);
+        self.row_list
+    }
+}
+
+// Tables inside a cell are read as tables of their own, and their text is left out of the cell.
+pub(crate) fn find_tables(html: &str) -> Vec<Table> {
+    let mut table_list = Vec::new();
+    // The open tables, innermost last
+    let mut stack: Vec<OpenTable> = Vec::new();
+    let mut skip_depth = 0;
+    for event in markup::events(html) {
+        match event {
+            Event::Start { name, .. } if name == "script" || name == "style" => skip_depth += 1,
+            Event::End { name } if name == "script" || name == "style" => {
+                skip_depth = usize::saturating_sub(skip_depth, 1)
+            }
+            Event::Start { name, .. } if name == "table" => stack.push(OpenTable::default()),
+            Event::End { name } if name == "table" => {
+                if let Some(table) = stack.pop() {
+                    table_list.push(table.finish());
+                }
+            }
+            Event::Start { name, .. } if name == "tr" => {
+                if let Some(table) = stack.last_mut() {
+                    finish_row(&mut table.row_list, table.row.take(), table.cell.take());
+                    table.row = Some(Vec::new());
+                }
+            }
+            Event::End { name } if name == "tr" => {
+                if let Some(table) = stack.last_mut() {
+                    finish_row(&mut table.row_list, table.row.take(), table.cell.take());
+                }
+            }
+            Event::Start {
+                name, attributes, ..
+            } if name == "td" || name == "th" => {
+                if let Some(table) = stack.last_mut() {
+                    let row = table.row.get_or_insert_with(Vec::new);
+                    finish_cell(row, table.cell.take());
+                    let colspan = attributes
+                        .get("colspan")
+                        .and_then(|colspan| colspan.trim().parse::<usize>().ok())
+                        .unwrap_or(1)
+                        .clamp(1, MAX_COLSPAN);
+                    table.cell = Some((String::new(), colspan));
+                }
+            }
+            Event::End { name } if name == "td" || name == "th" => {
+                if let Some(OpenTable {
+                    row: Some(row),
+                    cell,
+                    ..
+                }) = stack.last_mut()
+                {
+                    finish_cell(row, cell.take());
+                }
+            }
+            // Line breaks and block elements inside a cell separate words
+            Event::Start { name, .. } if matches!(name.as_str(), "br" | "p" | "div" | "li") => {
+                if let Some(OpenTable {
+                    cell: Some((text, _)),
+                    ..
+                }) = stack.last_mut()
+                {
+                    text.push(' ');
+                }
+            }
+            Event::Text(value) if skip_depth == 0 => {
+                if let Some(OpenTable {
+                    cell: Some((text, _)),
+                    ..
+                }) = stack.last_mut()
+                {
+                    text.push_str(&value);
+                }
+            }
+            _ => {}
+        }
+    }
+    // Tables left open at the end of the page
+    while let Some(table) = stack.pop() {
+        table_list.push(table.finish());
+    }
+    table_list
+}
+
+fn finish_cell(row: &mut Vec<String>, cell: Option<(String, usize)>) {
+    if let Some((text, colspan)) = cell {
+        row.push(text.split_whitespace().join(" "));
+        row.extend(core::iter::repeat_n(String::new(), colspan - 1));
+    }
+}
+
+fn finish_row(row_list: &mut Table, row: Option<Vec<String>>, cell: Option<(String, usize)>) {
+    if let Some(mut row) = row {
+        finish_cell(&mut row, cell);
+        if row.iter().any(|field| !field.is_empty()) {
+            row_list.push(row);
+        }
+    }
+}
+
+fn has_header(table: &Table) -> bool {
+    table.iter().take(5).any(|row| {
+        row.iter()
+            .filter(|field| Column::from_header(field).is_some())
+            .count()
+            >= 2
+    })
+}
+
+// The results table, if the page has a table
+pub(crate) fn choose_results_table(table_list: Vec<Table>) -> Option<Table> {
+    table_list.into_iter().max_by_key(|table| {
+        let wide_row_count = table.iter().filter(|row| row.len() >= 3).count();
+        (has_header(table), wide_row_count)
+    })
+}
+
+pub fn to_result_lines(html: &str) -> Result<Vec<String>, anyhow::Error> {
+    let table = choose_results_table(find_tables(html))
+        .ok_or_else(|| anyhow::anyhow!("Page has no <table> of results"))?;
+    Ok(rows_to_lines(&table))
+}
@@ -0,0 +1,54 @@
+// The string table behind `Token`. Each distinct token string is stored once, and a token is
+// just its index, so tokens copy, hash, and compare as numbers. The table is shared by every
+// `Config` and thread.
+//
+// Only members' names and cities, the bundled nicknames and first names, and tokens made with
+// `Token::new` are added. Words in race results are only looked up (see `Lookup`), since a word
+// no member has can't match anyone, so reading more results never grows the table.
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock, RwLockReadGuard};
+
+#[derive(Default)]
+struct Interner {
+    str_list: Vec<Box<str>>,
+    str_to_id: HashMap<Box<str>, u32>,
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+pub(crate) fn intern(s: &str) -> u32 {
+    // Most tokens are already in the table, so look with a shared lock first
+    if let Some(id) = interner().read().unwrap().str_to_id.get(s) {
+        return *id;
+    }
+    let mut interner = interner().write().unwrap();
+    if let Some(id) = interner.str_to_id.get(s) {
+        return *id;
+    }
+    let id = u32::try_from(interner.str_list.len()).expect("too many distinct tokens");
+    interner.str_list.push(s.into());
+    interner.str_to_id.insert(s.into(), id);
+    id
+}
+
+pub(crate) fn with_str<R>(id: u32, f: impl FnOnce(&str) -> R) -> R {
+    f(&interner().read().unwrap().str_list[id as usize])
+}
+
+// Looks up many strings under one shared lock. Don't intern or format a token while holding one:
+// taking the lock again on the same thread can deadlock when another thread is waiting to intern.
+pub(crate) struct Lookup(RwLockReadGuard<'static, Interner>);
+
+impl Lookup {
+    pub fn new() -> Self {
+        Self(interner().read().unwrap())
+    }
+
+    pub fn get(&self, s: &str) -> Option<u32> {
+        self.0.str_to_id.get(s).copied()
+    }
+}
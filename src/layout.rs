@@ -0,0 +1,472 @@
+// Which column of a result line is which, from header rows such as
+// "Place<tab>Bib<tab>Name<tab>Age<tab>Hometown<tab>Finish". A header row applies to the lines
+// after it, until the next header row. Lines before any header row have no known layout, and
+// the matcher falls back to treating the whole line as a bag of tokens. So do lines that are
+// neither tab-separated nor aligned, since single spaces also separate the words of a name.
+//
+// Many timing companies align columns with spaces instead of tabs. When a section's lines line
+// up, they are sliced at the columns, which keeps "Smith, John" in one field and keeps empty
+// fields in place. Without a header row, the name and location columns are guessed.
+
+use std::collections::HashSet;
+
+use itertools::Itertools;
+
+use crate::intern::Lookup;
+use crate::name_table::NameTable;
+use crate::{is_any_separator, Token};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Column {
+    Place,
+    Bib,
+    Name,
+    Location,
+    Age,
+    Gender,
+    Division,
+    ChipTime,
+    GunTime,
+    Time,
+}
+
+impl Column {
+    pub fn from_header(field: &str) -> Option<Column> {
+        let name = field
+            .trim()
+            .trim_end_matches('.')
+            .replace('_', " ")
+            .to_lowercase();
+        match name.as_str() {
+            "place" | "pl" | "overall" | "overall place" | "ovr" | "pos" | "position" => {
+                Some(Column::Place)
+            }
+            "bib" | "bib #" | "bib no" | "#" | "no" | "number" => Some(Column::Bib),
+            "name" | "runner" | "athlete" | "participant" | "full name" | "first"
+            | "first name" | "last" | "last name" | "surname" => Some(Column::Name),
+            "hometown" | "city" | "residence" | "location" | "from" | "city/state"
+            | "city, state" | "state" | "home" => Some(Column::Location),
+            "age" => Some(Column::Age),
+            "sex" | "gender" | "g" | "s" | "m/f" => Some(Column::Gender),
+            "div" | "division" | "category" | "cat" | "age group" | "ag" => Some(Column::Division),
+            "chip" | "chip time" | "chiptime" | "net" | "net time" | "nettime" => {
+                Some(Column::ChipTime)
+            }
+            "gun" | "gun time" | "guntime" | "clock" | "clock time" => Some(Column::GunTime),
+            "time" | "finish" | "finish time" | "official time" | "result" => Some(Column::Time),
+            _ => None,
+        }
+    }
+}
+
+// A line without digits that names at least two known columns
+fn parse_header(line: &str) -> Option<Vec<Option<Column>>> {
+    if line.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    // A header row may be comma-separated even when the results below it aren't
+    let column_list = split_fields_in(line, line.contains(','))
+        .iter()
+        .map(|field| Column::from_header(field))
+        .collect::<Vec<_>>();
+    (column_list.iter().flatten().count() >= 2).then_some(column_list)
+}
+
+// Tab-separated if there are tabs (keeping empty columns, so they line up with the header),
+// then columns aligned with spaces (where a name may be "Last, First"), and finally single words.
+pub(crate) fn split_fields(line: &str) -> Vec<&str> {
+    split_fields_in(line, false)
+}
+
+// Like `split_fields`, but comma-separated, before falling back to single words, when `commas`.
+// A comma alone doesn't make a line comma-separated: "12 Jane Doe Seattle, WA 25:01" isn't.
+fn split_fields_in(line: &str, commas: bool) -> Vec<&str> {
+    if line.contains('\t') {
+        return line.split('\t').map(str::trim).collect();
+    }
+    let field_list: Vec<&str> = if line.contains("  ") {
+        line.split("  ").collect()
+    } else if commas && line.contains(',') {
+        line.split(',').collect()
+    } else {
+        line.split_whitespace().collect()
+    };
+    field_list
+        .into_iter()
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .collect()
+}
+
+// Rows from a structured file (CSV, an HTML table) as tab-separated lines. Line breaks and
+// tabs inside a field become spaces.
+pub(crate) fn rows_to_lines(row_list: &[Vec<String>]) -> Vec<String> {
+    let header_index = row_list.iter().position(|row| {
+        row.iter()
+            .filter(|field| Column::from_header(field).is_some())
+            .count()
+            >= 2
+    });
+    row_list
+        .iter()
+        .enumerate()
+        .map(|(index, row)| {
+            row.iter()
+                .map(|field| {
+                    let field = field.split_whitespace().join(" ");
+                    // A header such as "5K Split" would no longer read as a header row, and
+                    // isn't a column the matcher uses anyway
+                    if Some(index) == header_index
+                        && Column::from_header(&field).is_none()
+                        && field.chars().any(|c| c.is_ascii_digit())
+                    {
+                        String::new()
+                    } else {
+                        field
+                    }
+                })
+                .join("\t")
+        })
+        .collect()
+}
+
+// Tokens from a line's name columns and its location columns
+pub(crate) struct ColumnTokens {
+    pub name: HashSet<Token>,
+    pub city: HashSet<Token>,
+}
+
+// Lines from one header row (or the start) up to the next header row
+struct Section {
+    column_list: Option<Vec<Option<Column>>>,
+    // True when `column_list` comes from a header row rather than from guessing
+    from_header: bool,
+    // Where each space-aligned column starts (in chars), when the lines line up
+    start_list: Option<Vec<usize>>,
+    // True when every line has the same number of commas, and no tabs or runs of spaces
+    comma_separated: bool,
+}
+
+pub(crate) struct Layout {
+    section_list: Vec<Section>,
+    // For each line, its section and whether it is the section's header row
+    line_section_list: Vec<(usize, bool)>,
+}
+
+impl Layout {
+    pub fn detect(result_lines: &[String]) -> Layout {
+        let mut line_section_list = Vec::with_capacity(result_lines.len());
+        let mut section_start_list = vec![0];
+        for (line_index, line) in result_lines.iter().enumerate() {
+            let is_header = parse_header(line).is_some();
+            if is_header && line_index > 0 {
+                section_start_list.push(line_index);
+            }
+            line_section_list.push((section_start_list.len() - 1, is_header));
+        }
+        section_start_list.push(result_lines.len());
+
+        let section_list = section_start_list
+            .iter()
+            .tuple_windows()
+            .map(|(&start, &end)| Section::new(&result_lines[start..end]))
+            .collect();
+        Layout {
+            section_list,
+            line_section_list,
+        }
+    }
+
+    pub fn is_header(&self, line_index: usize) -> bool {
+        self.line_section_list[line_index].1
+    }
+
+    // Sliced at the section's space-aligned columns, if it has them
+    pub fn fields<'a>(&self, line_index: usize, line: &'a str) -> Vec<&'a str> {
+        let section = &self.section_list[self.line_section_list[line_index].0];
+        match &section.start_list {
+            Some(start_list) => slice_fields(line, start_list),
+            None => split_fields_in(line, section.comma_separated),
+        }
+    }
+
+    // The line's fields, each with its column, when a header row names them
+    pub fn columns<'a>(
+        &self,
+        line_index: usize,
+        line: &'a str,
+    ) -> Option<Vec<(Option<Column>, &'a str)>> {
+        let section = &self.section_list[self.line_section_list[line_index].0];
+        if !section.from_header {
+            return None;
+        }
+        self.any_columns(line_index, line)
+    }
+
+    // Like `columns`, but also with columns guessed from a space-aligned layout
+    fn any_columns<'a>(
+        &self,
+        line_index: usize,
+        line: &'a str,
+    ) -> Option<Vec<(Option<Column>, &'a str)>> {
+        let (section_index, is_header) = self.line_section_list[line_index];
+        if is_header {
+            return None;
+        }
+        let section = &self.section_list[section_index];
+        let column_list = section.column_list.as_ref()?;
+        // A line without tabs under a tab-separated header can't be split into its columns
+        if section.start_list.is_none() && !line.contains('\t') {
+            return None;
+        }
+        Some(
+            column_list
+                .iter()
+                .copied()
+                .zip(self.fields(line_index, line))
+                .collect(),
+        )
+    }
+
+    // Only when there is a name column. Without a location column, any token may be the city.
+    pub fn column_tokens(
+        &self,
+        lookup: &Lookup,
+        line_index: usize,
+        line: &str,
+    ) -> Option<ColumnTokens> {
+        let column_field_list = self.any_columns(line_index, line)?;
+        let tokens = |column: Column| -> HashSet<Token> {
+            column_field_list
+                .iter()
+                .filter(|(field_column, _)| *field_column == Some(column))
+                .flat_map(|(_, field)| field.split(is_any_separator))
+                .filter_map(|s| Token::find_in(lookup, s))
+                .collect()
+        };
+        let has = |column: Column| {
+            column_field_list
+                .iter()
+                .any(|(field_column, _)| *field_column == Some(column))
+        };
+        if !has(Column::Name) {
+            return None;
+        }
+        let city = if has(Column::Location) {
+            tokens(Column::Location)
+        } else {
+            line.split(is_any_separator)
+                .filter_map(|s| Token::find_in(lookup, s))
+                .collect()
+        };
+        Some(ColumnTokens {
+            name: tokens(Column::Name),
+            city,
+        })
+    }
+}
+
+impl Section {
+    fn new(line_list: &[String]) -> Section {
+        let header = line_list.first().and_then(|line| parse_header(line));
+        let start_list = fixed_width_starts(line_list);
+        let (column_list, from_header) = match (&header, &start_list) {
+            (Some(_), Some(start_list)) => (
+                Some(
+                    slice_fields(&line_list[0], start_list)
+                        .into_iter()
+                        .map(Column::from_header)
+                        .collect(),
+                ),
+                true,
+            ),
+            // Single spaces also separate the words of a name or city, so only tabs will do
+            (Some(column_list), None) if line_list[0].contains('\t') => {
+                (Some(column_list.clone()), true)
+            }
+            (Some(_), None) => (None, false),
+            (None, Some(start_list)) => (guess_columns(line_list, start_list), false),
+            (None, None) => (None, false),
+        };
+        Section {
+            column_list,
+            from_header,
+            start_list,
+            comma_separated: is_comma_separated(line_list),
+        }
+    }
+}
+
+// CSV-like lines, such as "12,Jane Doe,Seattle,25:01", all have the same number of commas, and
+// at least two (three columns), so that lines that each have one "City, ST" aren't taken for
+// them. A lone line can't show that, so there must be at least two lines.
+fn is_comma_separated(line_list: &[String]) -> bool {
+    let comma_count_list = line_list
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            (!line.contains('\t') && !line.contains("  ")).then(|| line.matches(',').count())
+        })
+        .collect::<Option<Vec<_>>>();
+    match comma_count_list.as_deref() {
+        Some([first, rest @ ..]) => {
+            *first >= 2 && !rest.is_empty() && rest.iter().all(|count| count == first)
+        }
+        _ => false,
+    }
+}
+
+// Space-aligned columns are separated by positions that are blank on (nearly) every line. A
+// few lines, such as a title, may run across them. At least three columns must show up on most
+// lines, and most lines must be padded with runs of spaces, so that single-spaced text whose
+// words happen to line up isn't mistaken for a table.
+fn fixed_width_starts(line_list: &[String]) -> Option<Vec<usize>> {
+    let line_list = line_list
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>();
+    if line_list.len() < MIN_FIXED_WIDTH_LINES || line_list.iter().any(|line| line.contains('\t')) {
+        return None;
+    }
+    let padded_count = line_list
+        .iter()
+        .filter(|line| line.trim().contains("  "))
+        .count();
+    if padded_count * 2 < line_list.len() {
+        return None;
+    }
+
+    let mut filled_count_list = Vec::<usize>::new();
+    for line in line_list.iter() {
+        for (position, c) in line.chars().enumerate() {
+            if filled_count_list.len() <= position {
+                filled_count_list.resize(position + 1, 0);
+            }
+            if !c.is_whitespace() {
+                filled_count_list[position] += 1;
+            }
+        }
+    }
+    let tolerance = line_list.len() / 20;
+    let mut start_list = Vec::new();
+    let mut in_gap = true;
+    for (position, filled_count) in filled_count_list.into_iter().enumerate() {
+        let is_gap = filled_count <= tolerance;
+        if in_gap && !is_gap {
+            start_list.push(position);
+        }
+        in_gap = is_gap;
+    }
+    if start_list.len() < 3 {
+        return None;
+    }
+    // Whatever comes before the first column belongs to it
+    start_list[0] = 0;
+
+    let full_count = line_list
+        .iter()
+        .filter(|line| {
+            slice_fields(line, &start_list)
+                .iter()
+                .filter(|field| !field.is_empty())
+                .count()
+                >= 3
+        })
+        .count();
+    (full_count * 2 >= line_list.len()).then_some(start_list)
+}
+
+const MIN_FIXED_WIDTH_LINES: usize = 4;
+
+// Each field runs from its column's start to the next column's start, trimmed. Empty fields are
+// kept, so that fields stay lined up with their columns.
+fn slice_fields<'a>(line: &'a str, start_list: &[usize]) -> Vec<&'a str> {
+    let byte_at = |position: usize| {
+        line.char_indices()
+            .nth(position)
+            .map_or(line.len(), |(byte, _)| byte)
+    };
+    let byte_list = start_list.iter().map(|start| byte_at(*start)).collect_vec();
+    byte_list
+        .iter()
+        .enumerate()
+        .map(|(index, start)| {
+            let end = byte_list.get(index + 1).copied().unwrap_or(line.len());
+            line[*start..end].trim()
+        })
+        .collect()
+}
+
+// Without a header row, the first column of words (such as "Smith, John"), along with any
+// columns of names right after it (such as a separate last name), is taken to be the name, and
+// the next column of words the location. A column of single letters is more likely a gender.
+// A column after the name counts as names when at least half its words are in the name table,
+// so a hometown column such as "Salt Lake City" isn't merged into the name.
+fn guess_columns(line_list: &[String], start_list: &[usize]) -> Option<Vec<Option<Column>>> {
+    let field_lists = line_list
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| slice_fields(line, start_list))
+        .collect_vec();
+    let is_word_column = |index: usize| {
+        let field_list = field_lists
+            .iter()
+            .filter_map(|field_list| field_list.get(index))
+            .filter(|field| !field.is_empty())
+            .collect_vec();
+        let word_count = field_list
+            .iter()
+            .filter(|field| {
+                field.chars().filter(|c| c.is_alphabetic()).count() >= 2
+                    && field
+                        .chars()
+                        .all(|c| c.is_alphabetic() || " ,.'-".contains(c))
+            })
+            .count();
+        field_list.len() * 2 >= field_lists.len() && word_count * 10 >= field_list.len() * 9
+    };
+    let word_column_list = (0..start_list.len())
+        .filter(|index| is_word_column(*index))
+        .collect_vec();
+    let is_name_column = |index: usize| {
+        let name_table = NameTable::bundled();
+        let word_list = field_lists
+            .iter()
+            .filter_map(|field_list| field_list.get(index))
+            .flat_map(|field| field.split(is_any_separator))
+            .filter(|word| !word.is_empty())
+            .collect_vec();
+        let name_count = word_list
+            .iter()
+            .filter(|word| {
+                Token::to_canonical(word).is_ok_and(|word| name_table.prob(&word).is_some())
+            })
+            .count();
+        name_count * 2 >= word_list.len()
+    };
+    let first_name_index = *word_column_list.first()?;
+    // Side-by-side columns of names, such as First and Last, are all name
+    let name_count = 1 + word_column_list
+        .iter()
+        .enumerate()
+        .skip(1)
+        .take_while(|(offset, index)| {
+            **index == first_name_index + offset && is_name_column(**index)
+        })
+        .count();
+    let name_range = first_name_index..first_name_index + name_count;
+    let location_index = word_column_list.get(name_count).copied();
+    Some(
+        (0..start_list.len())
+            .map(|index| {
+                if name_range.contains(&index) {
+                    Some(Column::Name)
+                } else if Some(index) == location_index {
+                    Some(Column::Location)
+                } else {
+                    None
+                }
+            })
+            .collect(),
+    )
+}
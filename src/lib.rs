@@ -3,35 +3,70 @@
 use include_flate::flate;
 use num_enum::TryFromPrimitive;
 
+pub mod csv;
+pub mod encoding;
+pub mod evaluate;
+mod evidence;
+pub mod fields;
+pub mod generate;
+pub mod html;
+mod intern;
+mod layout;
+mod markup;
+mod members;
+mod name_table;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+mod records;
+pub mod report;
+pub mod scoring;
+pub mod season;
+pub mod spreadsheet;
+pub mod stream;
+pub mod sweep;
 mod tests;
+mod zip;
 
 extern crate alloc;
 
 use core::fmt;
+use core::str::FromStr;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::ops::Range;
 use std::{
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufReader},
     path::Path,
 };
 
-use alloc::{rc::Rc, string::String, string::ToString, vec::Vec};
+use alloc::{string::String, string::ToString, sync::Arc, vec::Vec};
 use anyinput::anyinput;
 use core::{
     cmp::Ordering,
     hash::{Hash, Hasher},
 };
 use core::{f32::consts::E, iter::repeat};
+pub use encoding::Encoding;
+use evidence::RaceFields;
+use fields::{Gender, ResultFields};
 use itertools::Itertools;
+use layout::{ColumnTokens, Layout};
+use name_table::NameTable;
+use report::escape_html;
+pub use report::{GroupBy, OutputFormat};
+pub use spreadsheet::SheetOptions;
 
 // // cmk file is not local
-flate!(static NAME_TO_PROB_STR: str from "data/name_probability.tsv");
 flate!(static NICKNAMES_STR: str from "data/nicknames.txt");
+flate!(static NAME_GENDER_STR: str from "data/name_gender.tsv");
 flate!(pub static SAMPLE_MEMBERS_STR: str from "data/sample_members.txt");
 flate!(pub static SAMPLE_RESULTS_STR: str from "data/sample_results.txt");
 
+const EM_MAX_ITERATIONS: usize = 100;
+const EM_TOLERANCE: f32 = 1e-6;
+const RENORMALIZE_ITERATIONS: usize = 10;
+
 fn is_comma_or_tab(c: char) -> bool {
     c == ',' || c == '\t'
 }
@@ -47,30 +82,74 @@ fn is_any_separator(c: char) -> bool {
     is_comma_or_tab(c) || is_slash_or_ampersand(c) || is_whitespace_or_dash(c)
 }
 
-#[derive(Eq, PartialEq, Hash, Clone)]
-pub struct Token(String);
+// An interned token (see `intern`): a number standing for its canonical string
+#[derive(Eq, PartialEq, Hash, Clone, Copy)]
+pub struct Token(u32);
 
 impl fmt::Debug for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.0)
+        intern::with_str(self.0, |s| write!(f, "{s:?}"))
     }
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        intern::with_str(self.0, |s| f.write_str(s))
     }
 }
 
+// Up to this many bytes, an ASCII string is made canonical without allocating
+const STACK_TOKEN_LEN: usize = 64;
+
 impl Token {
     pub fn new(s: &str) -> Self {
-        Self(Token::to_canonical(s).unwrap())
+        Token::new_or_error(s).unwrap()
+    }
+
+    // The token for a word, if it is one and it has been interned. Doesn't intern the word.
+    pub fn find(s: &str) -> Option<Self> {
+        Token::find_in(&intern::Lookup::new(), s)
+    }
+
+    pub(crate) fn find_in(lookup: &intern::Lookup, s: &str) -> Option<Self> {
+        let id = if Token::is_short_ascii(s) {
+            let mut buffer = [0; STACK_TOKEN_LEN];
+            lookup.get(Token::ascii_canonical(s, &mut buffer)?)
+        } else {
+            lookup.get(&Token::to_canonical(s).ok()?)
+        };
+        id.map(Self)
+    }
+
+    fn is_short_ascii(s: &str) -> bool {
+        s.is_ascii() && s.len() <= STACK_TOKEN_LEN
+    }
+
+    // Like `to_canonical`, for a short ASCII string, but written into `buffer`
+    fn ascii_canonical<'a>(s: &str, buffer: &'a mut [u8; STACK_TOKEN_LEN]) -> Option<&'a str> {
+        let mut len = 0;
+        for byte in s.bytes().filter(|byte| !matches!(byte, b'.' | b'\'')) {
+            if !byte.is_ascii_alphabetic() {
+                return None;
+            }
+            buffer[len] = byte.to_ascii_uppercase();
+            len += 1;
+        }
+        // A-Z, so always UTF-8
+        core::str::from_utf8(&buffer[..len])
+            .ok()
+            .filter(|canonical| !canonical.is_empty())
     }
 
     // A-Za-z . '
     // internally we got to uppercase, remove . and ' and it can't be then empty.
+    // Accented Latin letters lose their accents, so "Renée" is RENEE.
     pub fn to_canonical(s: &str) -> Result<String, anyhow::Error> {
-        let s = s.to_uppercase().replace(['.', '\''], "");
+        let mut s = s.to_uppercase();
+        if !s.is_ascii() {
+            s = s.chars().map(Token::fold_accent).collect();
+        }
+        let s = s.replace(['.', '\''], "");
         if s.is_empty() || s.chars().any(|c| !c.is_ascii_alphabetic()) {
             Err(anyhow::anyhow!(
                 "String must be alphabetic with (ignored . and ') and then not empty, not \"{}\".",
@@ -81,11 +160,40 @@ impl Token {
         }
     }
 
+    fn fold_accent(c: char) -> String {
+        match c {
+            'À'..='Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+            'Æ' => "AE",
+            'Ç' | 'Ć' | 'Č' => "C",
+            'Ð' | 'Ď' | 'Đ' => "D",
+            'È'..='Ë' | 'Ē' | 'Ė' | 'Ę' | 'Ě' => "E",
+            'Ğ' => "G",
+            'Ì'..='Ï' | 'Ī' | 'İ' => "I",
+            'Ł' | 'Ľ' => "L",
+            'Ñ' | 'Ń' | 'Ň' => "N",
+            'Ò'..='Ö' | 'Ø' | 'Ő' => "O",
+            'Œ' => "OE",
+            'Ř' => "R",
+            'Ś' | 'Ş' | 'Š' => "S",
+            'Þ' => "TH",
+            'Ť' | 'Ţ' => "T",
+            'Ù'..='Ü' | 'Ū' | 'Ů' | 'Ű' => "U",
+            'Ý' | 'Ÿ' => "Y",
+            'Ź' | 'Ż' | 'Ž' => "Z",
+            _ => return c.to_string(),
+        }
+        .to_string()
+    }
+
+    // Looks the canonical string up before allocating anything
     pub fn new_or_error(s: &str) -> Result<Token, anyhow::Error> {
-        match Token::to_canonical(s) {
-            Ok(s) => Ok(Self(s)),
-            Err(e) => Err(e),
+        if Token::is_short_ascii(s) {
+            let mut buffer = [0; STACK_TOKEN_LEN];
+            if let Some(canonical) = Token::ascii_canonical(s, &mut buffer) {
+                return Ok(Self(intern::intern(canonical)));
+            }
         }
+        Ok(Self(intern::intern(&Token::to_canonical(s)?)))
     }
 }
 
@@ -154,37 +262,61 @@ pub fn prob(logodds: f32) -> f32 {
 
 pub struct TokenToCoincidence {
     token_to_prob: HashMap<Token, f32>,
+    // Looked up after `token_to_prob` (the bundled names, for `default_names`)
+    name_table: Option<NameTable>,
     default: f32,
 }
 
 impl TokenToCoincidence {
+    // The bundled name table is searched in place (see `name_table`), so this is cheap
     pub fn default_names() -> Self {
-        let mut name_to_coincidence = HashMap::new();
-        for line in NAME_TO_PROB_STR.lines().skip(1) {
-            let (name, prob) = line.split(is_comma_or_tab).collect_tuple().unwrap();
-            let name = Token::new(name);
-            let prob = prob.parse::<f32>().unwrap();
-            name_to_coincidence.insert(name, prob);
-        }
+        let name_table = NameTable::bundled();
         // override for single letter names
         let single_letter_prob = 0.01f32;
-        for single_letter in "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars() {
-            let name = Token::new(&single_letter.to_string());
-            name_to_coincidence.insert(name, single_letter_prob);
-        }
-        let min_prob = name_to_coincidence.values().fold(1.0f32, |a, b| a.min(*b));
+        let name_to_coincidence = "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+            .chars()
+            .map(|single_letter| (Token::new(&single_letter.to_string()), single_letter_prob))
+            .collect::<HashMap<_, _>>();
         Self {
             token_to_prob: name_to_coincidence,
-            default: min_prob,
+            name_table: Some(name_table),
+            default: name_table.min_prob().min(single_letter_prob),
         }
     }
 }
 impl TokenToCoincidence {
+    // Each token's probability looked up once, so that scoring reads a map instead of searching
+    // the name table
+    fn for_tokens<'a>(&self, token_iter: impl Iterator<Item = &'a Token>) -> Self {
+        Self {
+            token_to_prob: token_iter.map(|token| (*token, self.prob(token))).collect(),
+            name_table: None,
+            default: self.default,
+        }
+    }
+
     pub fn prob(&self, name: &Token) -> f32 {
-        *self.token_to_prob.get(name).unwrap_or(&self.default)
+        if let Some(prob) = self.token_to_prob.get(name) {
+            return *prob;
+        }
+        self.name_table
+            .and_then(|name_table| intern::with_str(name.0, |s| name_table.prob(s)))
+            .unwrap_or(self.default)
     }
 }
 
+// How often each first name belongs to a woman
+fn extract_name_to_prob_female() -> HashMap<Token, f32> {
+    NAME_GENDER_STR
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let (name, prob_female) = line.split(is_comma_or_tab).collect_tuple().unwrap();
+            (Token::new(name), prob_female.parse::<f32>().unwrap())
+        })
+        .collect()
+}
+
 fn extract_name_to_nicknames_set() -> HashMap<Token, HashSet<Token>> {
     let mut name_to_nickname_set = HashMap::<Token, HashSet<Token>>::new();
 
@@ -215,13 +347,13 @@ fn extract_name_to_nicknames_set() -> HashMap<Token, HashSet<Token>> {
         for left in left_and_right[0].iter() {
             for right in left_and_right[1].iter() {
                 name_to_nickname_set
-                    .entry(left.clone())
+                    .entry(*left)
                     .or_insert_with(HashSet::new)
-                    .insert(right.clone());
+                    .insert(*right);
                 name_to_nickname_set
-                    .entry(right.clone())
+                    .entry(*right)
                     .or_insert_with(HashSet::new)
-                    .insert(left.clone());
+                    .insert(*left);
             }
         }
     }
@@ -236,6 +368,25 @@ pub struct Config {
     pub stop_words_points: f32,
     pub threshold_probability: f32,
     pub override_results_count: Option<usize>,
+    pub estimate_prob_member_in_race: bool,
+    pub assignment: Assignment,
+    pub group_by: GroupBy,
+    pub output_format: OutputFormat,
+    // When grouping by member, flag members with more than one line at least this likely
+    pub review_probability: f32,
+    // Turns members' birth years into ages. Without it, ages aren't used as evidence.
+    pub race_year: Option<u32>,
+    // Chance that a member's line gives their age (within a year)
+    pub prob_age_right: f32,
+    // Chance that a member's line gives their gender
+    pub prob_gender_right: f32,
+    // Chance that a member's line gives the bib from the members file
+    pub prob_bib_right: f32,
+    // How many result lines make up one result, one by default. None detects results split
+    // across lines.
+    pub lines_per_record: Option<usize>,
+    // About how many result lines streaming scores at a time (see `stream`)
+    pub stream_chunk_lines: usize,
 }
 
 impl Default for Config {
@@ -248,13 +399,24 @@ impl Default for Config {
             stop_words_points: 3.0,
             threshold_probability: 0.01,
             override_results_count: None,
+            estimate_prob_member_in_race: false,
+            assignment: Assignment::Independent,
+            group_by: GroupBy::Line,
+            output_format: OutputFormat::Html,
+            review_probability: 0.1,
+            race_year: None,
+            prob_age_right: 0.95,
+            prob_gender_right: 0.98,
+            prob_bib_right: 0.99,
+            lines_per_record: Some(1),
+            stream_chunk_lines: 10_000,
         }
     }
 }
 
 #[allow(non_camel_case_types)]
 #[allow(non_upper_case_globals)]
-#[derive(TryFromPrimitive)]
+#[derive(TryFromPrimitive, Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum IncludeCity {
     Yes = 0,
@@ -262,6 +424,28 @@ pub enum IncludeCity {
     Auto = 2,
 }
 
+// Independent scores every (result line, member) pair on its own, so a member can show up on
+// several lines and several members can claim one line. OneToOne keeps, per race, at most
+// one line per member and one member per line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assignment {
+    Independent,
+    OneToOne,
+}
+
+impl FromStr for IncludeCity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "yes" => Ok(IncludeCity::Yes),
+            "no" => Ok(IncludeCity::No),
+            "auto" => Ok(IncludeCity::Auto),
+            _ => anyhow::bail!("Include city should be 'yes', 'no', or 'auto', not '{s}'"),
+        }
+    }
+}
+
 impl IncludeCity {
     pub fn to_bool_list(&self) -> Vec<bool> {
         match self {
@@ -284,59 +468,166 @@ impl Config {
         result_lines: AnyIter<AnyString>,
         include_city: IncludeCity,
     ) -> Result<Vec<String>, anyhow::Error> {
-        self.assert_that_config_is_valid();
+        let member_index = self.index_members(member_lines)?;
+        self.find_matches_in(&member_index, result_lines, include_city)
+    }
 
-        let member_lines = member_lines
-            .map(|line| line.as_ref().to_string())
-            .collect_vec();
+    // Like `find_matches`, but with members already indexed by `index_members`.
+    #[anyinput]
+    pub fn find_matches_in(
+        &self,
+        member_index: &MemberIndex,
+        result_lines: AnyIter<AnyString>,
+        include_city: IncludeCity,
+    ) -> Result<Vec<String>, anyhow::Error> {
         let result_lines = result_lines
             .map(|line| line.as_ref().to_string())
             .collect_vec();
 
-        let results_as_tokens = self.tokenize_race_results(&result_lines);
+        let (prob_member_in_race, line_people_list) =
+            self.find_line_people_in(member_index, &result_lines, include_city, self.race_year)?;
+
+        let estimated_prob_member_in_race = self
+            .estimate_prob_member_in_race
+            .then_some(prob_member_in_race);
+        Ok(self.format_final_output(estimated_prob_member_in_race, line_people_list))
+    }
+
+    // Returns the prob_member_in_race used and the result lines with at least one likely member.
+    pub(crate) fn find_line_people(
+        &self,
+        member_lines: &[String],
+        result_lines: &[String],
+        include_city: IncludeCity,
+    ) -> Result<(f32, Vec<LinePeople>), anyhow::Error> {
+        let member_index = self.index_members(member_lines)?;
+        self.find_line_people_in(&member_index, result_lines, include_city, self.race_year)
+    }
+
+    pub(crate) fn find_line_people_in(
+        &self,
+        member_index: &MemberIndex,
+        result_lines: &[String],
+        include_city: IncludeCity,
+        race_year: Option<u32>,
+    ) -> Result<(f32, Vec<LinePeople>), anyhow::Error> {
+        self.assert_that_config_is_valid();
+
+        let parsed = self.parse_results(result_lines);
+        let ParsedResults {
+            records,
+            results_as_tokens,
+            fields_list,
+            column_tokens_list,
+            ..
+        } = &parsed;
+        let result_lines = records.line_list.as_slice();
 
         // Look for tokens in the race results that are too common to be useful
-        let (name_stop_words, city_stop_words, city_to_coincidence) =
-            self.find_stop_words(&results_as_tokens);
+        let results_count = self.results_count(results_as_tokens.len());
+        let (name_stop_words, city_stop_words, city_to_coincidence) = self.find_stop_words(
+            &self.count_result_tokens(results_as_tokens),
+            results_count,
+            &member_index.name_to_coincidence,
+        );
 
-        let results_count = self.results_count(&results_as_tokens);
-        let prior_points = log_odds(self.prob_member_in_race / results_count as f32);
+        let race_fields = RaceFields::new(fields_list, race_year);
 
         let mut best = None;
         for include_city_as_bool in include_city.to_bool_list() {
-            let token_to_person_list = self.index_person_list(
-                &member_lines,
-                &name_stop_words,
-                &city_stop_words,
-                include_city_as_bool,
-            )?;
-
-            let line_people_list = self.find_matching_people_for_each_result_line(
-                &result_lines,
-                &results_as_tokens,
+            let person_list = member_index.person_list(include_city_as_bool);
+            let token_to_person_list =
+                self.index_person_list(person_list, &name_stop_words, &city_stop_words);
+            let bib_to_person_list = Self::index_bibs(person_list);
+
+            let line_points_list = self.find_matching_people_for_each_result_line(
+                result_lines,
+                results_as_tokens,
+                column_tokens_list,
                 &token_to_person_list,
+                &bib_to_person_list,
+                &member_index.name_to_coincidence,
                 &city_to_coincidence,
+                &race_fields,
             );
 
+            let prob_member_in_race = if self.estimate_prob_member_in_race {
+                self.estimate_prob_member_in_race(
+                    &line_points_list,
+                    member_index.len(),
+                    results_count,
+                )
+            } else {
+                self.prob_member_in_race
+            };
+            let prior_points = log_odds(prob_member_in_race / results_count as f32);
+
+            let line_people_list = self.find_line_people_list(&line_points_list, prior_points);
+
             let delta_sum = line_people_list
                 .iter()
                 .map(|line_people| log_odds(line_people.max_prob) - prior_points)
                 .sum::<f32>();
 
-            best = if let Some((best_delta_sum, best_line_people_list)) = best {
+            best = if let Some((best_delta_sum, best_prob_member_in_race, best_line_people_list)) =
+                best
+            {
                 if delta_sum > best_delta_sum {
-                    Some((delta_sum, line_people_list))
+                    Some((delta_sum, prob_member_in_race, line_people_list))
                 } else {
-                    Some((best_delta_sum, best_line_people_list))
+                    Some((
+                        best_delta_sum,
+                        best_prob_member_in_race,
+                        best_line_people_list,
+                    ))
                 }
             } else {
-                Some((delta_sum, line_people_list))
+                Some((delta_sum, prob_member_in_race, line_people_list))
             }
         }
-        let line_people_list = best.unwrap().1; // always OK
-        let final_output = self.format_final_output(line_people_list);
+        let (_, prob_member_in_race, mut line_people_list) = best.unwrap(); // always OK
+
+        // Joined lines are reported at their first line
+        for line_people in line_people_list.iter_mut() {
+            line_people.fields = fields_list[line_people.line_index].clone();
+            line_people.line_index = records.start_list[line_people.line_index];
+        }
+        Ok((prob_member_in_race, line_people_list))
+    }
+
+    // Joins results that were split across several lines, then tokenizes them and parses
+    // their fields. Fields depend on headings and header rows, so every line is parsed in order.
+    pub(crate) fn parse_results(&self, result_lines: &[String]) -> ParsedResults {
+        let records = records::segment(result_lines, self.lines_per_record);
+        let result_lines = records.line_list.as_slice();
+        let layout = Layout::detect(result_lines);
+        let fields_list = fields::parse_result_fields_in(&layout, result_lines);
+        let lookup = intern::Lookup::new();
+        let results_as_tokens = self.tokenize_race_results(&lookup, result_lines);
+        let column_tokens_list = result_lines
+            .iter()
+            .enumerate()
+            .map(|(line_index, line)| layout.column_tokens(&lookup, line_index, line))
+            .collect_vec();
+        ParsedResults {
+            records,
+            results_as_tokens,
+            layout,
+            fields_list,
+            column_tokens_list,
+        }
+    }
 
-        Ok(final_output)
+    // How many results there are after joining lines, leaving out header rows and blank lines
+    pub(crate) fn result_record_count(&self, result_lines: &[String]) -> usize {
+        let records = records::segment(result_lines, self.lines_per_record);
+        let layout = Layout::detect(&records.line_list);
+        records
+            .line_list
+            .iter()
+            .enumerate()
+            .filter(|(line_index, line)| !line.trim().is_empty() && !layout.is_header(*line_index))
+            .count()
     }
 
     fn assert_that_config_is_valid(&self) {
@@ -354,14 +645,19 @@ impl Config {
         );
     }
 
+    // Only words already interned (such as members' names) become tokens
     #[anyinput]
-    fn tokenize_race_results(&self, result_lines: AnyIter<AnyString>) -> Vec<HashSet<Token>> {
+    fn tokenize_race_results(
+        &self,
+        lookup: &intern::Lookup,
+        result_lines: AnyIter<AnyString>,
+    ) -> Vec<HashSet<Token>> {
         result_lines
             .map(|result_line| {
                 let result_line = result_line.as_ref();
                 let token_set: HashSet<Token> = result_line
                     .split(is_any_separator)
-                    .filter_map(|s| Token::new_or_error(s).ok())
+                    .filter_map(|s| Token::find_in(lookup, s))
                     .collect();
                 // println!("token_set={:?}", token_set);
                 token_set
@@ -376,7 +672,7 @@ impl Config {
                 .iter()
                 .flatten()
                 .fold(HashMap::new(), |mut acc, token| {
-                    *acc.entry(token.clone()).or_insert(0) += 1;
+                    *acc.entry(*token).or_insert(0) += 1;
                     acc
                 });
         // let mut result_token_to_line_count_vec: Vec<(String, isize)> =
@@ -389,14 +685,18 @@ impl Config {
     fn annotate_line<T: Score>(result_line: &str, all_points: &T, missing: &str) -> String {
         let annotations = all_points.annotations();
 
-        // Find the first location of every token in the result line
+        // Find the first location of every token in the result line. The lookup's lock is
+        // released before any token is formatted, since formatting takes the lock again.
         let mut token_to_range: HashMap<Token, Range<usize>> = HashMap::new();
-        for s in result_line.split(is_any_separator) {
-            if let Ok(token) = Token::new_or_error(s) {
-                token_to_range.entry(token).or_insert_with(|| {
-                    let offset = s.as_ptr() as usize - result_line.as_ptr() as usize;
-                    offset..offset + s.len()
-                });
+        {
+            let lookup = intern::Lookup::new();
+            for s in result_line.split(is_any_separator) {
+                if let Some(token) = Token::find_in(&lookup, s) {
+                    token_to_range.entry(token).or_insert_with(|| {
+                        let offset = s.as_ptr() as usize - result_line.as_ptr() as usize;
+                        offset..offset + s.len()
+                    });
+                }
             }
         }
 
@@ -408,7 +708,7 @@ impl Config {
                 list.push(annotation);
             } else {
                 let list = vec![annotation.clone()];
-                token_to_annotation_list.insert(annotation.token.clone(), list);
+                token_to_annotation_list.insert(annotation.token, list);
             }
         }
 
@@ -417,11 +717,11 @@ impl Config {
             .keys()
             .map(|token| {
                 let range_or_none = token_to_range.get(token).cloned();
-                (token.clone(), range_or_none)
+                (*token, range_or_none)
             })
             .collect_vec();
 
-        // Sort the locations/tokens from back to found (with None first)
+        // Sort the locations/tokens from front to back (with None first)
         token_and_range.sort_by(|a, b| {
             let a_range_or_none = a.1.as_ref();
             let b_range_or_none = b.1.as_ref();
@@ -429,12 +729,15 @@ impl Config {
                 (None, None) => Ordering::Equal,
                 (None, Some(_)) => Ordering::Less,
                 (Some(_), None) => Ordering::Greater,
-                (Some(a_range), Some(b_range)) => b_range.start.cmp(&a_range.start),
+                (Some(a_range), Some(b_range)) => a_range.start.cmp(&b_range.start),
             }
         });
 
-        // for every location/token (from the back), insert the annotationS into the result line
-        let mut annotated = result_line.to_string();
+        // for every location/token (from the front), insert the annotationS into the result line,
+        // escaping the text around them. Missing tokens go at the end.
+        let mut annotated = String::new();
+        let mut missing_list = Vec::new();
+        let mut end = 0;
         for (token, range_or_none) in token_and_range {
             let delta_list = &token_to_annotation_list[&token]
                 .iter()
@@ -450,65 +753,185 @@ impl Config {
                 "style=\"background-color: red; color: white;\""
             };
             if let Some(range) = range_or_none {
-                let s = &result_line[range.clone()];
-                annotated.replace_range(
-                    range.clone(),
-                    &format!("<mark {color}>{s}<sup>{pts_str} pts</sup></mark>"),
-                );
+                let s = escape_html(&result_line[range.clone()]);
+                annotated.push_str(&escape_html(&result_line[end..range.start]));
+                annotated.push_str(&format!("<mark {color}>{s}<sup>{pts_str} pts</sup></mark>"));
+                end = range.end;
             } else {
-                annotated.push_str(&format!(
+                missing_list.push(format!(
                     "<mark {color}> -{missing}: {token}<sup>{pts_str} pts</sup></mark>"
                 ));
             }
         }
+        annotated.push_str(&escape_html(&result_line[end..]));
+        annotated.extend(missing_list);
+        for (note, delta) in all_points.notes() {
+            let color = if delta >= 0.0 {
+                ""
+            } else {
+                "style=\"background-color: red; color: white;\""
+            };
+            annotated.push_str(&format!(
+                "<mark {color}> {note}<sup>{delta:+.2} pts</sup></mark>"
+            ));
+        }
         annotated
     }
 
+    // Lines are scored in parallel with the "parallel" feature, and listed in line order either way
     #[allow(clippy::too_many_arguments)]
     #[anyinput]
     fn find_matching_people_for_each_result_line(
         &self,
         result_lines: &[String],
         results_as_tokens: &[HashSet<Token>],
-        token_to_person_list: &HashMap<Token, Vec<Rc<Person>>>,
+        column_tokens_list: &[Option<ColumnTokens>],
+        token_to_person_list: &HashMap<Token, Vec<Arc<Person>>>,
+        bib_to_person_list: &HashMap<String, Vec<Arc<Person>>>,
+        name_to_coincidence: &TokenToCoincidence,
         city_to_coincidence: &TokenToCoincidence,
-    ) -> Vec<LinePeople> {
-        let results_count = self.results_count(results_as_tokens);
-        let prior_points = log_odds(self.prob_member_in_race / results_count as f32);
-
-        let mut line_people_list: Vec<LinePeople> = Vec::new();
-
-        // for each line in the results
-        for (result_line, result_tokens) in result_lines.iter().zip(results_as_tokens) {
-            // find people with at least one token in common with the result line
-            let person_set = result_tokens
-                .iter()
-                .filter_map(|token| token_to_person_list.get(token))
-                .flatten()
-                .collect::<HashSet<_>>();
-
-            let mut line_people: Option<LinePeople> = None;
-            for person in person_set.iter() {
-                let person = *person;
+        race_fields: &RaceFields,
+    ) -> Vec<LinePoints> {
+        let find_line_points = |line_index: usize| {
+            self.find_matching_people_for_result_line(
+                line_index,
+                &result_lines[line_index],
+                &results_as_tokens[line_index],
+                column_tokens_list[line_index].as_ref(),
+                token_to_person_list,
+                bib_to_person_list,
+                name_to_coincidence,
+                city_to_coincidence,
+                race_fields,
+            )
+        };
+        let line_count = result_lines.len().min(results_as_tokens.len());
+
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            (0..line_count)
+                .into_par_iter()
+                .filter_map(find_line_points)
+                .collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            (0..line_count).filter_map(find_line_points).collect()
+        }
+    }
 
-                let name_points = person.name_points(result_tokens, &self.name_to_coincidence);
-                let city_points = person.city_points(result_tokens, city_to_coincidence);
-                let all_points: Vec<Box<dyn Score>> =
-                    vec![Box::new(name_points), Box::new(city_points)];
-                let all_points =
-                    IndScoreList::collect_and_title(all_points, "name+city".to_string());
+    #[allow(clippy::too_many_arguments)]
+    fn find_matching_people_for_result_line(
+        &self,
+        line_index: usize,
+        result_line: &str,
+        result_tokens: &HashSet<Token>,
+        column_tokens: Option<&ColumnTokens>,
+        token_to_person_list: &HashMap<Token, Vec<Arc<Person>>>,
+        bib_to_person_list: &HashMap<String, Vec<Arc<Person>>>,
+        name_to_coincidence: &TokenToCoincidence,
+        city_to_coincidence: &TokenToCoincidence,
+        race_fields: &RaceFields,
+    ) -> Option<LinePoints> {
+        // find people with at least one token in common with the result line (or their bib),
+        // in member order
+        let person_list = result_tokens
+            .iter()
+            .filter_map(|token| token_to_person_list.get(token))
+            .chain(
+                race_fields
+                    .bib(line_index)
+                    .and_then(|bib| bib_to_person_list.get(bib)),
+            )
+            .flatten()
+            .unique_by(|person| person.id)
+            .sorted_by_key(|person| person.id);
+
+        // When the layout is known, names only come from name columns and cities from
+        // location columns, so "Bergman, OR" isn't a Bergman.
+        let (name_tokens, city_tokens) = match column_tokens {
+            Some(column_tokens) => (&column_tokens.name, &column_tokens.city),
+            None => (result_tokens, result_tokens),
+        };
 
-                // let name_points = person.name_points(result_tokens, &self.name_to_coincidence);
-                // let city_points = person.city_points(result_tokens, city_to_coincidence);
+        let mut person_points_list = Vec::new();
+        for person in person_list {
+            let name_points = person.name_points(name_tokens, name_to_coincidence);
+            let city_points = person.city_points(city_tokens, city_to_coincidence);
+            let flags = race_fields.flags(person, line_index, name_points.delta());
+            let mut all_points: Vec<Box<dyn Score>> =
+                vec![Box::new(name_points), Box::new(city_points)];
+            all_points.extend(race_fields.scores(self, person, line_index));
+            let all_points = IndScoreList::collect_and_title(all_points, "name+city".to_string());
+
+            person_points_list.push(PersonPoints {
+                person: person.clone(),
+                all_points,
+                flags,
+            });
+        }
+        if person_points_list.is_empty() {
+            return None;
+        }
+        Some(LinePoints {
+            line_index,
+            line: result_line.to_string(),
+            person_points_list,
+        })
+    }
 
-                let post_points = prior_points + all_points.delta();
-                let post_prob = prob(post_points);
+    // Turn points into probabilities (given the prior) and keep only the people above the threshold
+    fn find_line_people_list(
+        &self,
+        line_points_list: &[LinePoints],
+        prior_points: f32,
+    ) -> Vec<LinePeople> {
+        let mut prob_lists = line_points_list
+            .iter()
+            .map(|line_points| {
+                line_points
+                    .person_points_list
+                    .iter()
+                    .map(|person_points| prob(prior_points + person_points.all_points.delta()))
+                    .collect_vec()
+            })
+            .collect_vec();
+        let rejected_pairs = if self.assignment == Assignment::OneToOne {
+            Self::assign_one_to_one(line_points_list, &mut prob_lists)
+        } else {
+            HashSet::new()
+        };
 
-                if post_prob > self.threshold_probability {
+        let mut line_people_list: Vec<LinePeople> = Vec::new();
+        for (line_list_index, (line_points, prob_list)) in
+            line_points_list.iter().zip(prob_lists).enumerate()
+        {
+            let mut line_people: Option<LinePeople> = None;
+            for (person_index, (person_points, post_prob)) in line_points
+                .person_points_list
+                .iter()
+                .zip(prob_list)
+                .enumerate()
+            {
+                let person = &person_points.person;
+                let all_points = &person_points.all_points;
+
+                // Flagged pairs are reported even when unlikely, so someone looks at them, but
+                // not when the one-to-one assignment gave the line or member to another pair
+                if rejected_pairs.contains(&(line_list_index, person_index)) {
+                    continue;
+                }
+                if post_prob > self.threshold_probability || !person_points.flags.is_empty() {
                     // let annotated_result_line =
                     //     Config::annotate_line(result_line, &all_points, "Missing");
-                    let annotated_input_person =
-                        Config::annotate_line(&person.input_pretty, &all_points, "Nickname");
+                    let mut annotated_input_person =
+                        Config::annotate_line(&person.input_pretty, all_points, "Nickname");
+                    for flag in person_points.flags.iter() {
+                        annotated_input_person.push_str(&format!(
+                            " <mark style=\"background-color: orange;\">{flag}</mark>"
+                        ));
+                    }
                     let show_work = format!(
                         "
                     <tr>
@@ -521,21 +944,26 @@ impl Config {
                         all_delta = all_points.delta()
                     );
 
+                    let person_prob = PersonProb {
+                        person: person.clone(),
+                        prob: post_prob,
+                        delta: all_points.delta(),
+                        flags: person_points.flags.clone(),
+                        show_work,
+                    };
                     match &mut line_people {
                         None => {
                             line_people = Some(LinePeople {
-                                line: result_line.to_string(),
+                                line_index: line_points.line_index,
+                                line: line_points.line.clone(),
                                 max_prob: post_prob,
-                                person_prob_list: vec![(person.clone(), post_prob, show_work)],
+                                person_prob_list: vec![person_prob],
+                                fields: ResultFields::default(),
                             })
                         }
                         Some(line_people) => {
                             line_people.max_prob = line_people.max_prob.max(post_prob);
-                            line_people.person_prob_list.push((
-                                person.clone(),
-                                post_prob,
-                                show_work,
-                            ));
+                            line_people.person_prob_list.push(person_prob);
                         }
                     };
                 }
@@ -548,13 +976,110 @@ impl Config {
         line_people_list
     }
 
+    // Re-normalize so that no member's probabilities (over lines) and no line's probabilities
+    // (over members) add up to more than 1. Then, from the most probable pair down, keep a
+    // pair only if neither its line nor its member is already taken.
+    // Returns the (line, person) index pairs that lost their line or member to another pair
+    fn assign_one_to_one(
+        line_points_list: &[LinePoints],
+        prob_lists: &mut [Vec<f32>],
+    ) -> HashSet<(usize, usize)> {
+        for _ in 0..RENORMALIZE_ITERATIONS {
+            let mut id_to_total = HashMap::<usize, f32>::new();
+            for (line_points, prob_list) in line_points_list.iter().zip(prob_lists.iter()) {
+                for (person_points, prob) in line_points.person_points_list.iter().zip(prob_list) {
+                    *id_to_total.entry(person_points.person.id).or_insert(0.0) += prob;
+                }
+            }
+            for (line_points, prob_list) in line_points_list.iter().zip(prob_lists.iter_mut()) {
+                for (person_points, prob) in line_points
+                    .person_points_list
+                    .iter()
+                    .zip(prob_list.iter_mut())
+                {
+                    *prob /= id_to_total[&person_points.person.id].max(1.0);
+                }
+                let line_total = prob_list.iter().sum::<f32>();
+                prob_list
+                    .iter_mut()
+                    .for_each(|prob| *prob /= line_total.max(1.0));
+            }
+        }
+
+        let mut pair_list = Vec::new();
+        for (line_index, line_points) in line_points_list.iter().enumerate() {
+            for (person_index, person_points) in line_points.person_points_list.iter().enumerate() {
+                pair_list.push((line_index, person_index, person_points.person.id));
+            }
+        }
+        // Ties go to the earlier line and then the earlier member so that results are repeatable
+        pair_list.sort_by(|a, b| {
+            prob_lists[b.0][b.1]
+                .partial_cmp(&prob_lists[a.0][a.1])
+                .unwrap()
+                .then(a.0.cmp(&b.0))
+                .then(a.2.cmp(&b.2))
+        });
+        let mut taken_lines = HashSet::new();
+        let mut taken_ids = HashSet::new();
+        let mut rejected_pairs = HashSet::new();
+        for (line_index, person_index, id) in pair_list {
+            if taken_lines.contains(&line_index) || taken_ids.contains(&id) {
+                prob_lists[line_index][person_index] = 0.0;
+                rejected_pairs.insert((line_index, person_index));
+            } else {
+                taken_lines.insert(line_index);
+                taken_ids.insert(id);
+            }
+        }
+        rejected_pairs
+    }
+
+    // Expectation-maximization: Given the current guess of prob_member_in_race, find the
+    // expected number of members in the race (each member finishes at most once). Use that
+    // to re-guess prob_member_in_race. The configured value acts as one pseudo-member so that
+    // the estimate never reaches 0 or 1.
+    fn estimate_prob_member_in_race(
+        &self,
+        line_points_list: &[LinePoints],
+        member_count: usize,
+        results_count: usize,
+    ) -> f32 {
+        let mut prob_member_in_race = self.prob_member_in_race;
+        for _ in 0..EM_MAX_ITERATIONS {
+            let prior_points = log_odds(prob_member_in_race / results_count as f32);
+            let mut id_to_expected = HashMap::<usize, f32>::new();
+            for person_points in line_points_list
+                .iter()
+                .flat_map(|line_points| line_points.person_points_list.iter())
+            {
+                *id_to_expected.entry(person_points.person.id).or_insert(0.0) +=
+                    prob(prior_points + person_points.all_points.delta());
+            }
+            let expected_member_count = id_to_expected
+                .values()
+                .map(|expected| expected.min(1.0))
+                .sum::<f32>();
+            let next =
+                (expected_member_count + self.prob_member_in_race) / (member_count + 1) as f32;
+            let done = (next - prob_member_in_race).abs() < EM_TOLERANCE;
+            prob_member_in_race = next;
+            if done {
+                break;
+            }
+        }
+        prob_member_in_race
+    }
+
     fn extract_dist_list(
         &self,
         name_or_city_phrase: &str,
         token_to_nickname_set: &HashMap<Token, HashSet<Token>>,
     ) -> Result<Vec<Dist>, anyhow::Error> {
+        // A tab-separated members file may have cities such as "Portland, OR"
         name_or_city_phrase
             .split(is_whitespace_or_dash)
+            .map(|name_or_city| name_or_city.trim_matches(','))
             .map(|name_or_city| self.split_token(name_or_city, token_to_nickname_set))
             .collect::<Result<Vec<_>, _>>()
     }
@@ -606,121 +1131,251 @@ impl Config {
     }
 
     fn insert_into_map(
-        token_to_person_list: &mut HashMap<Token, Vec<Rc<Person>>>,
+        token_to_person_list: &mut HashMap<Token, Vec<Arc<Person>>>,
         token: &Token,
-        person: &Rc<Person>,
+        person: &Arc<Person>,
     ) {
         token_to_person_list
-            .entry(token.clone())
+            .entry(*token)
             .or_insert(Vec::new())
             .push(person.clone());
     }
 
-    #[allow(clippy::too_many_arguments)]
+    // Parse the members once so that many races can share the work.
     #[anyinput]
-    fn index_person_list(
+    pub fn index_members(
         &self,
         member_lines: AnyIter<AnyString>,
-        name_stop_words: &HashSet<Token>,
-        city_stop_words: &HashSet<Token>,
-        include_city_as_bool: bool,
-    ) -> Result<HashMap<Token, Vec<Rc<Person>>>, anyhow::Error> {
+    ) -> Result<MemberIndex, anyhow::Error> {
         let name_to_nickname_set = extract_name_to_nicknames_set();
-
-        let mut token_to_person_list = HashMap::<Token, Vec<Rc<Person>>>::new();
+        let city_to_nickname_set = HashMap::<Token, HashSet<Token>>::new(); // currently empty
+        let name_to_prob_female = extract_name_to_prob_female();
+
+        let mut member_index = MemberIndex {
+            with_city: Vec::new(),
+            without_city: Vec::new(),
+            name_to_coincidence: TokenToCoincidence {
+                token_to_prob: HashMap::new(),
+                name_table: None,
+                default: self.name_to_coincidence.default,
+            },
+        };
+        let mut header = None;
         for (id, line) in member_lines.enumerate() {
             let line = line.as_ref();
-            let fields = line.split(is_comma_or_tab).collect_vec();
-            if fields.len() != 3 {
-                anyhow::bail!(
-                    "Line should be First,Last,City separated by tab or comma, not '{line}'"
-                );
+            if id == 0 {
+                header = members::MemberColumns::from_header(line);
+                if header.is_some() {
+                    continue;
+                }
             }
-            let name = format!("{} {}", fields[0], fields[1]);
-            let name_dist_list = self.extract_dist_list(&name, &name_to_nickname_set)?;
-
-            let city = if include_city_as_bool { fields[2] } else { "" };
-            let city_to_nickname_set = HashMap::<Token, HashSet<Token>>::new(); // currently empty
-            let city_dist_list = self.extract_dist_list(city, &city_to_nickname_set)?;
-
-            let person = Rc::new(Person {
-                name_dist_list,
-                city_dist_list,
-                id,
-                input_pretty: format!("{} {} @ {}", fields[0], fields[1], fields[2]),
-            });
+            let record = match &header {
+                Some(header) => header.parse(line)?,
+                None => members::parse_positional(line)?,
+            };
+            let name = format!("{} {}", record.first, record.last);
+            // Without a gender, guess from the first name
+            let prob_female = match record.gender {
+                Some(gender) => Some(if gender == Gender::Female { 1.0 } else { 0.0 }),
+                None => record
+                    .first
+                    .split(is_any_separator)
+                    .find_map(Token::find)
+                    .and_then(|first| name_to_prob_female.get(&first).copied()),
+            };
+            let input_pretty = format!("{} {} @ {}", record.first, record.last, record.city);
+
+            for include_city_as_bool in [true, false] {
+                let name_dist_list = self.extract_dist_list(&name, &name_to_nickname_set)?;
+                let city = if include_city_as_bool {
+                    record.city
+                } else {
+                    ""
+                };
+                let city_dist_list = self.extract_dist_list(city, &city_to_nickname_set)?;
+
+                let person = Arc::new(Person {
+                    name_dist_list,
+                    city_dist_list,
+                    birth_year: record.birth_year,
+                    prob_female,
+                    gender: record.gender,
+                    bib: record.bib.clone(),
+                    id,
+                    input_pretty: input_pretty.clone(),
+                });
+                if include_city_as_bool {
+                    member_index.with_city.push(person);
+                } else {
+                    member_index.without_city.push(person);
+                }
+            }
+        }
+        member_index.name_to_coincidence = self.name_to_coincidence.for_tokens(
+            member_index
+                .with_city
+                .iter()
+                .flat_map(|person| person.name_dist_list.iter())
+                .flat_map(|name_dist| name_dist.tokens()),
+        );
+        Ok(member_index)
+    }
 
+    fn index_bibs(person_list: &[Arc<Person>]) -> HashMap<String, Vec<Arc<Person>>> {
+        let mut bib_to_person_list = HashMap::<String, Vec<Arc<Person>>>::new();
+        for person in person_list {
+            if let Some(bib) = &person.bib {
+                bib_to_person_list
+                    .entry(bib.clone())
+                    .or_default()
+                    .push(person.clone());
+            }
+        }
+        bib_to_person_list
+    }
+
+    fn index_person_list(
+        &self,
+        person_list: &[Arc<Person>],
+        name_stop_words: &HashSet<Token>,
+        city_stop_words: &HashSet<Token>,
+    ) -> HashMap<Token, Vec<Arc<Person>>> {
+        let mut token_to_person_list = HashMap::<Token, Vec<Arc<Person>>>::new();
+        for person in person_list {
             person
                 .name_dist_list
                 .iter()
                 .flat_map(|name_dist| name_dist.tokens())
                 .filter(|name| !name_stop_words.contains(name))
-                .for_each(|name| Self::insert_into_map(&mut token_to_person_list, name, &person));
+                .for_each(|name| Self::insert_into_map(&mut token_to_person_list, name, person));
 
             person
                 .city_dist_list
                 .iter()
                 .flat_map(|city_dist| city_dist.tokens())
                 .filter(|city| !city_stop_words.contains(city))
-                .for_each(|city| Self::insert_into_map(&mut token_to_person_list, city, &person));
+                .for_each(|city| Self::insert_into_map(&mut token_to_person_list, city, person));
         }
-        Ok(token_to_person_list)
+        token_to_person_list
     }
 
-    fn results_count(&self, results_as_tokens: &[HashSet<Token>]) -> usize {
+    fn results_count(&self, line_count: usize) -> usize {
         match self.override_results_count {
             Some(results_count) => results_count,
-            None => results_as_tokens.len(),
+            None => line_count,
         }
     }
 
+    // `result_token_and_line_count_list` gives, for each token, how many result lines have it
     fn find_stop_words(
         &self,
-        results_as_tokens: &[HashSet<Token>],
+        result_token_and_line_count_list: &HashMap<Token, usize>,
+        results_count: usize,
+        name_to_coincidence: &TokenToCoincidence,
     ) -> (HashSet<Token>, HashSet<Token>, TokenToCoincidence) {
-        let results_count = self.results_count(results_as_tokens);
         let city_coincidence_default = 1f32 / (results_count + 2) as f32;
 
-        let result_token_and_line_count_list = self.count_result_tokens(results_as_tokens);
-
         let mut name_stop_words = HashSet::<Token>::new();
         let mut city_stop_words = HashSet::<Token>::new();
         let mut city_to_coincidence = TokenToCoincidence {
             token_to_prob: HashMap::new(),
+            name_table: None,
             default: city_coincidence_default,
         };
 
         for (token, count) in result_token_and_line_count_list.iter() {
-            let results_count = self.results_count(results_as_tokens);
             let city_coincidence = (*count + 1) as f32 / (results_count + 2) as f32;
             city_to_coincidence
                 .token_to_prob
-                .insert(token.clone(), city_coincidence);
+                .insert(*token, city_coincidence);
             let city_points_contains = delta_one(true, city_coincidence, self.total_right);
             if city_points_contains < self.stop_words_points {
-                city_stop_words.insert(token.clone());
+                city_stop_words.insert(*token);
             }
             let name_points_contains =
-                delta_one_name(true, token, self.total_right, &self.name_to_coincidence);
+                delta_one_name(true, token, self.total_right, name_to_coincidence);
             if name_points_contains < self.stop_words_points {
-                name_stop_words.insert(token.clone());
+                name_stop_words.insert(*token);
             }
         }
         (name_stop_words, city_stop_words, city_to_coincidence)
     }
 
+    fn format_final_output(
+        &self,
+        estimated_prob_member_in_race: Option<f32>,
+        line_people_list: Vec<LinePeople>,
+    ) -> Vec<String> {
+        match self.output_format {
+            OutputFormat::Json => {
+                let json = match self.group_by {
+                    GroupBy::Line => report::to_json(
+                        estimated_prob_member_in_race,
+                        Some(&report::line_reports(&line_people_list)),
+                        None,
+                    ),
+                    GroupBy::Member => report::to_json(
+                        estimated_prob_member_in_race,
+                        None,
+                        Some(&report::member_reports(
+                            &line_people_list,
+                            self.review_probability,
+                        )),
+                    ),
+                };
+                vec![json]
+            }
+            OutputFormat::Text => {
+                let mut line_list = Vec::new();
+                if let Some(prob_member_in_race) = estimated_prob_member_in_race {
+                    line_list.push(format!(
+                        "Estimated prob_member_in_race: {:.2}%",
+                        prob_member_in_race * 100.0
+                    ));
+                }
+                line_list.extend(match self.group_by {
+                    GroupBy::Line => {
+                        report::lines_to_text(&report::line_reports(&line_people_list))
+                    }
+                    GroupBy::Member => report::members_to_text(&report::member_reports(
+                        &line_people_list,
+                        self.review_probability,
+                    )),
+                });
+                line_list
+            }
+            OutputFormat::Html => {
+                let mut line_list = Vec::new();
+                if let Some(prob_member_in_race) = estimated_prob_member_in_race {
+                    line_list.push(format!(
+                        "<p>Estimated prob_member_in_race: {:.2}%</p>",
+                        prob_member_in_race * 100.0
+                    ));
+                }
+                line_list.extend(match self.group_by {
+                    GroupBy::Line => self.format_html_by_line(line_people_list),
+                    GroupBy::Member => report::members_to_html(&report::member_reports(
+                        &line_people_list,
+                        self.review_probability,
+                    )),
+                });
+                line_list
+            }
+        }
+    }
+
     // cmk this should be a method of LinePeople ???
     // cmk might as well return a string
-    fn format_final_output(&self, line_people_list: Vec<LinePeople>) -> Vec<String> {
+    fn format_html_by_line(&self, line_people_list: Vec<LinePeople>) -> Vec<String> {
         let mut line_list = Vec::new();
         for line_people in line_people_list.iter() {
-            line_list.push(format!("<pre>{}</pre>", line_people.line));
-            let mut person_prob_list = line_people.person_prob_list.clone();
-            person_prob_list.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            line_list.push(format!("<pre>{}</pre>", escape_html(&line_people.line)));
+            let mut person_prob_list = line_people.person_prob_list.iter().collect_vec();
+            person_prob_list.sort_by(|a, b| b.prob.partial_cmp(&a.prob).unwrap());
 
             line_list.push("<div class=\"indented-table\"><table>".to_string());
-            for (_person, _prob, show_work) in person_prob_list.iter() {
-                line_list.push(show_work.clone());
+            for person_prob in person_prob_list.iter() {
+                line_list.push(person_prob.show_work.clone());
             }
             line_list.push("</table></div>".to_string());
         }
@@ -758,15 +1413,26 @@ impl Dist {
 struct Person {
     name_dist_list: Vec<Dist>,
     city_dist_list: Vec<Dist>,
+    birth_year: Option<u32>,
+    // 1.0 or 0.0 when the members file gives a gender, otherwise a guess from the first name
+    prob_female: Option<f32>,
+    // From the members file
+    gender: Option<Gender>,
+    bib: Option<String>,
     id: usize,
     input_pretty: String,
 }
 
-trait Score: core::fmt::Debug {
+// Send and Sync, so that lines can be scored in parallel
+trait Score: core::fmt::Debug + Send + Sync {
     fn delta(&self) -> f32;
     fn html(&self) -> String;
     fn annotations(&self) -> Vec<Annotation>;
     fn title(&self) -> String;
+    // Evidence that isn't a token, such as age, as (text, points)
+    fn notes(&self) -> Vec<(String, f32)> {
+        Vec::new()
+    }
 }
 
 #[derive(Clone)]
@@ -797,7 +1463,7 @@ impl SingleScore {
         let prob_right = *prob;
         let delta_inner = delta_one(contains, prob_coincidence, prob_right);
         SingleScore {
-            token: token.clone(),
+            token: *token,
             contains,
             prob_right,
             prob_coincidence,
@@ -810,7 +1476,7 @@ impl SingleScore {
 impl Score for SingleScore {
     fn annotations(&self) -> Vec<Annotation> {
         vec![Annotation {
-            token: self.token.clone(),
+            token: self.token,
             delta: self.delta,
         }]
     }
@@ -982,6 +1648,12 @@ impl Score for IndScoreList {
             .flat_map(|score| score.annotations())
             .collect()
     }
+    fn notes(&self) -> Vec<(String, f32)> {
+        self.score_list
+            .iter()
+            .flat_map(|score| score.notes())
+            .collect()
+    }
     fn delta(&self) -> f32 {
         self.delta
     }
@@ -1136,14 +1808,148 @@ impl PartialEq for Person {
     }
 }
 
+// Every member, parsed once with their city and once without it
+pub struct MemberIndex {
+    with_city: Vec<Arc<Person>>,
+    without_city: Vec<Arc<Person>>,
+    // `Config::name_to_coincidence` for every token in members' names
+    name_to_coincidence: TokenToCoincidence,
+}
+
+impl MemberIndex {
+    pub fn len(&self) -> usize {
+        self.with_city.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.with_city.is_empty()
+    }
+
+    fn person_list(&self, include_city_as_bool: bool) -> &[Arc<Person>] {
+        if include_city_as_bool {
+            &self.with_city
+        } else {
+            &self.without_city
+        }
+    }
+}
+
+struct PersonPoints {
+    person: Arc<Person>,
+    all_points: IndScoreList,
+    // Things someone should check, such as a bib that disagrees with the name
+    flags: Vec<String>,
+}
+
+pub(crate) struct ParsedResults {
+    records: records::Records,
+    results_as_tokens: Vec<HashSet<Token>>,
+    layout: Layout,
+    fields_list: Vec<ResultFields>,
+    column_tokens_list: Vec<Option<ColumnTokens>>,
+}
+
+struct LinePoints {
+    line_index: usize,
+    line: String,
+    person_points_list: Vec<PersonPoints>,
+}
+
 struct LinePeople {
+    line_index: usize,
     line: String,
     max_prob: f32,
-    person_prob_list: Vec<(Rc<Person>, f32, String)>,
+    person_prob_list: Vec<PersonProb>,
+    fields: ResultFields,
+}
+
+struct PersonProb {
+    person: Arc<Person>,
+    prob: f32,
+    delta: f32,
+    flags: Vec<String>,
+    show_work: String,
 }
 
+// Decoded from whatever encoding the file is in (see `encoding::decode_lines`)
 pub fn read_lines<P: AsRef<Path>>(path: P) -> io::Result<impl Iterator<Item = io::Result<String>>> {
-    Ok(BufReader::new(File::open(path)?).lines())
+    Ok(encoding::decode_lines(BufReader::new(File::open(path)?)))
+}
+
+pub fn read_all_lines<P: AsRef<Path>>(path: P) -> Result<Vec<String>, anyhow::Error> {
+    let path = path.as_ref();
+    read_lines(path)
+        .and_then(|lines| lines.collect())
+        .map_err(|e| anyhow::anyhow!("Reading '{}': {e}", path.display()))
+}
+
+// Members or results, read by file type: ".csv" as CSV (see `csv`), ".html" and ".htm" as saved
+// pages (see `html`), ".pdf" as PDF (see `pdf`, with the "pdf" feature), ".xlsx" and ".ods" as
+// spreadsheets (see `spreadsheet`), and anything else as text lines. A ".gz" file is unzipped
+// and read by the extension before it, as in "results.csv.gz". Text files come with the encoding
+// they were read in; spreadsheets and PDFs say their own.
+pub fn read_input<P: AsRef<Path>>(
+    path: P,
+    sheet_options: &SheetOptions,
+) -> Result<(Vec<String>, Option<Encoding>), anyhow::Error> {
+    let path = path.as_ref();
+    let reading_error = |e: anyhow::Error| anyhow::anyhow!("Reading '{}': {e}", path.display());
+    let bytes = std::fs::read(path).map_err(|e| reading_error(e.into()))?;
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy())
+        .unwrap_or_default();
+    input_from_bytes(&file_name, &bytes, sheet_options).map_err(reading_error)
+}
+
+// The lines of a file (or zip entry) named `name`, from its bytes (see `read_input`)
+pub fn input_from_bytes(
+    name: &str,
+    bytes: &[u8],
+    sheet_options: &SheetOptions,
+) -> Result<(Vec<String>, Option<Encoding>), anyhow::Error> {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) => (stem, extension.to_lowercase()),
+        None => (name, String::new()),
+    };
+    match extension.as_str() {
+        "gz" => input_from_bytes(stem, &gunzip(bytes)?, sheet_options),
+        "csv" => {
+            let (text, encoding) = encoding::decode(bytes);
+            Ok((csv::to_result_lines(&text)?, Some(encoding)))
+        }
+        "html" | "htm" => {
+            let (text, encoding) = encoding::decode(bytes);
+            Ok((html::to_result_lines(&text)?, Some(encoding)))
+        }
+        #[cfg(feature = "pdf")]
+        "pdf" => Ok((pdf::to_result_lines(bytes)?, None)),
+        #[cfg(not(feature = "pdf"))]
+        "pdf" => Err(anyhow::anyhow!(
+            "PDF input needs race-results built with the \"pdf\" feature"
+        )),
+        "xlsx" | "ods" => Ok((spreadsheet::to_lines(bytes, sheet_options)?, None)),
+        _ => {
+            let (text, encoding) = encoding::decode(bytes);
+            Ok((text.lines().map(str::to_string).collect(), Some(encoding)))
+        }
+    }
+}
+
+// Every member of a gzip file, one after another
+fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut decoded = Vec::new();
+    libflate::gzip::MultiDecoder::new(bytes)
+        .and_then(|mut decoder| io::Read::read_to_end(&mut decoder, &mut decoded))
+        .map_err(|e| anyhow::anyhow!("Not a readable gzip file: {e}"))?;
+    Ok(decoded)
+}
+
+pub fn read_input_lines<P: AsRef<Path>>(
+    path: P,
+    sheet_options: &SheetOptions,
+) -> Result<Vec<String>, anyhow::Error> {
+    Ok(read_input(path, sheet_options)?.0)
 }
 
 // cmk make the results paste in window small
@@ -0,0 +1,428 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use race_results::generate::{Generator, ResultLayout};
+use race_results::scoring::{PointsTable, Scoring};
+use race_results::{
+    evaluate, read_input, scoring, season, stream, sweep, Assignment, Config, Encoding, GroupBy,
+    IncludeCity, OutputFormat, SheetOptions,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Find club members in race results")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+// Matcher settings shared by every subcommand that runs the matcher
+#[derive(Args)]
+struct MatcherArgs {
+    /// yes, no, or auto
+    #[arg(long, default_value = "yes")]
+    include_city: IncludeCity,
+    /// Lowest probability the matcher reports
+    #[arg(long, default_value_t = Config::default().threshold_probability)]
+    threshold_probability: f32,
+    /// Estimate prob_member_in_race from each race
+    #[arg(long)]
+    estimate_prior: bool,
+    /// Report each member on at most one line and each line for at most one member
+    #[arg(long)]
+    one_to_one: bool,
+    /// Year of the race, so members' birth years can be checked against ages (batch and
+    /// score read it from each race's date)
+    #[arg(long)]
+    race_year: Option<u32>,
+    /// Lines that make up one result, when results were pasted with each result split across
+    /// lines
+    #[arg(long, default_value_t = 1)]
+    lines_per_record: usize,
+    /// Detect results split across lines and join each back into one
+    #[arg(long, conflicts_with = "lines_per_record")]
+    detect_records: bool,
+}
+
+// Spreadsheet inputs (.xlsx or .ods) may need a sheet and a header row
+#[derive(Args)]
+struct InputArgs {
+    /// Sheet of a members spreadsheet, by name or number (default: the first)
+    #[arg(long)]
+    members_sheet: Option<String>,
+    /// Header row of a members spreadsheet (1-based); rows above it are skipped
+    #[arg(long)]
+    members_header_row: Option<usize>,
+    /// Sheet of results spreadsheets, by name or number (default: the first)
+    #[arg(long)]
+    results_sheet: Option<String>,
+    /// Header row of results spreadsheets (1-based); rows above it are skipped
+    #[arg(long)]
+    results_header_row: Option<usize>,
+}
+
+impl InputArgs {
+    fn members(&self) -> SheetOptions {
+        SheetOptions {
+            sheet: self.members_sheet.clone(),
+            header_row: self.members_header_row,
+        }
+    }
+
+    fn results(&self) -> SheetOptions {
+        SheetOptions {
+            sheet: self.results_sheet.clone(),
+            header_row: self.results_header_row,
+        }
+    }
+}
+
+impl MatcherArgs {
+    fn config(&self) -> Config {
+        Config {
+            threshold_probability: self.threshold_probability,
+            estimate_prob_member_in_race: self.estimate_prior,
+            assignment: if self.one_to_one {
+                Assignment::OneToOne
+            } else {
+                Assignment::Independent
+            },
+            race_year: self.race_year,
+            lines_per_record: (!self.detect_records).then_some(self.lines_per_record),
+            ..Config::default()
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Match a members file against a results file and print the HTML report
+    Match {
+        members: PathBuf,
+        results: PathBuf,
+        #[command(flatten)]
+        matcher: MatcherArgs,
+        #[command(flatten)]
+        input: InputArgs,
+        /// line or member
+        #[arg(long, default_value = "line")]
+        group_by: GroupBy,
+        /// html, json, or text
+        #[arg(long, default_value = "html")]
+        format: OutputFormat,
+        /// Read a text results file twice, a chunk at a time, printing each matched line as
+        /// it's found (for races too big to hold in memory; text or json, by line)
+        #[arg(long)]
+        stream: bool,
+    },
+    /// Match a members file against every race in a manifest or archive and summarize the season
+    Batch {
+        members: PathBuf,
+        /// One "name<tab>date<tab>results file" line per race, or a .zip of results files
+        races: PathBuf,
+        #[command(flatten)]
+        matcher: MatcherArgs,
+        #[command(flatten)]
+        input: InputArgs,
+        /// Lowest probability that counts as having run a race
+        #[arg(long, default_value_t = 0.5)]
+        participation_probability: f32,
+        /// html, json, or text
+        #[arg(long, default_value = "html")]
+        format: OutputFormat,
+    },
+    /// Match every race in a manifest or archive, then score club points and teams for the season
+    Score {
+        members: PathBuf,
+        /// One "name<tab>date<tab>results file" line per race, or a .zip of results files
+        races: PathBuf,
+        #[command(flatten)]
+        matcher: MatcherArgs,
+        #[command(flatten)]
+        input: InputArgs,
+        /// Lowest probability that counts as having run a race
+        #[arg(long, default_value_t = 0.5)]
+        participation_probability: f32,
+        /// Points for the club's 1st, 2nd, ... finisher of each gender
+        #[arg(long, default_value = "10,8,6,5,4,3,2,1")]
+        points: PointsTable,
+        /// Points for finishers past the end of the points table
+        #[arg(long, default_value_t = Scoring::default().participation_points)]
+        participation_points: u32,
+        /// How many finishers of each gender count toward a team score
+        #[arg(long, default_value_t = Scoring::default().team_size)]
+        team_size: usize,
+        /// Write the leaderboard as CSV (default: print the CSV)
+        #[arg(long)]
+        csv: Option<PathBuf>,
+        /// Write each race's club finishers as CSV
+        #[arg(long)]
+        races_csv: Option<PathBuf>,
+        /// Write the leaderboard and races as a self-contained HTML page
+        #[arg(long)]
+        html: Option<PathBuf>,
+    },
+    /// Report precision, recall, and calibration over a directory of labelled cases
+    Evaluate {
+        cases: PathBuf,
+        #[command(flatten)]
+        matcher: MatcherArgs,
+        /// Comma-separated probability thresholds
+        #[arg(long, value_delimiter = ',')]
+        thresholds: Vec<f32>,
+    },
+    /// Sweep the threshold over labelled cases and write ROC and precision-recall curves
+    Sweep {
+        cases: PathBuf,
+        // threshold_probability is also the lowest threshold swept
+        #[command(flatten)]
+        matcher: MatcherArgs,
+        /// Write the curves as CSV (default: print the CSV)
+        #[arg(long)]
+        csv: Option<PathBuf>,
+        /// Write the charts as SVG
+        #[arg(long)]
+        svg: Option<PathBuf>,
+        /// Write the charts and table as a self-contained HTML page
+        #[arg(long)]
+        html: Option<PathBuf>,
+    },
+    /// Write made-up members, results, and labels (a case for `evaluate`) into a directory
+    Generate {
+        out_dir: PathBuf,
+        #[arg(long, default_value_t = Generator::default().seed)]
+        seed: u64,
+        #[arg(long, default_value_t = Generator::default().member_count)]
+        members: usize,
+        #[arg(long, default_value_t = Generator::default().result_count)]
+        results: usize,
+        /// How many members to hide in the results
+        #[arg(long, default_value_t = Generator::default().members_in_race)]
+        in_race: usize,
+        #[arg(long, default_value_t = Generator::default().prob_nickname)]
+        prob_nickname: f32,
+        #[arg(long, default_value_t = Generator::default().prob_typo)]
+        prob_typo: f32,
+        #[arg(long, default_value_t = Generator::default().prob_missing_city)]
+        prob_missing_city: f32,
+        /// tab, csv, or fixed
+        #[arg(long, default_value = "tab")]
+        layout: ResultLayout,
+    },
+}
+
+// Files that weren't UTF-8 are noted on stderr, so that a misread accent can be traced
+fn report_encoding(what: &str, encoding: Option<Encoding>) {
+    if let Some(encoding) = encoding.filter(|encoding| *encoding != Encoding::Utf8) {
+        eprintln!("Read {what} as {encoding}");
+    }
+}
+
+fn read_input_lines(
+    path: &Path,
+    sheet_options: &SheetOptions,
+) -> Result<Vec<String>, anyhow::Error> {
+    let (line_list, encoding) = read_input(path, sheet_options)?;
+    report_encoding(&format!("'{}'", path.display()), encoding);
+    Ok(line_list)
+}
+
+fn load_races(
+    path: &Path,
+    sheet_options: &SheetOptions,
+) -> Result<Vec<season::Race>, anyhow::Error> {
+    let race_list = season::load_races(path, sheet_options)?;
+    for race in &race_list {
+        report_encoding(&format!("race '{}'", race.name), race.encoding);
+    }
+    Ok(race_list)
+}
+
+// Prints each matched line as soon as it's found, as text or JSON Lines
+fn stream_matches(
+    config: &Config,
+    member_lines: &[String],
+    results: &Path,
+    include_city: IncludeCity,
+) -> Result<(), anyhow::Error> {
+    if config.group_by != GroupBy::Line || config.output_format == OutputFormat::Html {
+        anyhow::bail!("Streaming prints text or json by line");
+    }
+    let member_index = config.index_members(member_lines)?;
+    let token_counts = config.count_tokens(&member_index, stream::open_results(results)?)?;
+    report_encoding(&format!("'{}'", results.display()), token_counts.encoding);
+    config.stream_matches(
+        &member_index,
+        &token_counts,
+        stream::open_results(results)?,
+        include_city,
+        |line_report| match config.output_format {
+            OutputFormat::Json => println!("{}", line_report.to_json_line()),
+            _ => line_report
+                .to_text()
+                .iter()
+                .for_each(|line| println!("{line}")),
+        },
+    )?;
+    Ok(())
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    match Cli::parse().command {
+        Command::Match {
+            members,
+            results,
+            matcher,
+            input,
+            group_by,
+            format,
+            stream,
+        } => {
+            let member_lines = read_input_lines(&members, &input.members())?;
+            let config = Config {
+                group_by,
+                output_format: format,
+                ..matcher.config()
+            };
+            if stream {
+                stream_matches(&config, &member_lines, &results, matcher.include_city)?;
+            } else {
+                let result_lines = read_input_lines(&results, &input.results())?;
+                for line in config.find_matches(member_lines, result_lines, matcher.include_city)? {
+                    println!("{line}");
+                }
+            }
+        }
+        Command::Batch {
+            members,
+            races,
+            matcher,
+            input,
+            participation_probability,
+            format,
+        } => {
+            let member_lines = read_input_lines(&members, &input.members())?;
+            let race_list = load_races(&races, &input.results())?;
+            let season_report = season::find_season_matches(
+                &matcher.config(),
+                &member_lines,
+                &race_list,
+                matcher.include_city,
+                participation_probability,
+            )?;
+            match format {
+                OutputFormat::Html => print!("{}", season_report.to_html()),
+                OutputFormat::Json => println!("{}", season_report.to_json()),
+                OutputFormat::Text => print!("{}", season_report.to_text()),
+            }
+        }
+        Command::Score {
+            members,
+            races,
+            matcher,
+            input,
+            participation_probability,
+            points,
+            participation_points,
+            team_size,
+            csv,
+            races_csv,
+            html,
+        } => {
+            let member_lines = read_input_lines(&members, &input.members())?;
+            let race_list = load_races(&races, &input.results())?;
+            let season_report = season::find_season_matches(
+                &matcher.config(),
+                &member_lines,
+                &race_list,
+                matcher.include_city,
+                participation_probability,
+            )?;
+            let scoring = Scoring {
+                points_table: points,
+                participation_points,
+                team_size,
+            };
+            let standings = scoring::score(&scoring, &season_report, &race_list);
+            if csv.is_none() && races_csv.is_none() && html.is_none() {
+                print!("{}", standings.to_csv());
+            }
+            if let Some(csv) = csv {
+                fs::write(csv, standings.to_csv())?;
+            }
+            if let Some(races_csv) = races_csv {
+                fs::write(races_csv, standings.races_to_csv())?;
+            }
+            if let Some(html) = html {
+                fs::write(html, standings.to_html())?;
+            }
+        }
+        Command::Evaluate {
+            cases,
+            matcher,
+            thresholds,
+        } => {
+            let case_list = evaluate::load_cases(&cases)?;
+            let thresholds = if thresholds.is_empty() {
+                evaluate::DEFAULT_THRESHOLDS.to_vec()
+            } else {
+                thresholds
+            };
+            let report = evaluate::evaluate(
+                &matcher.config(),
+                &case_list,
+                matcher.include_city,
+                &thresholds,
+            )?;
+            print!("{report}");
+        }
+        Command::Sweep {
+            cases,
+            matcher,
+            csv,
+            svg,
+            html,
+        } => {
+            let case_list = evaluate::load_cases(&cases)?;
+            let report =
+                evaluate::evaluate(&matcher.config(), &case_list, matcher.include_city, &[])?;
+            let point_list = sweep::sweep(&report.prediction_list, report.pair_count);
+            if csv.is_none() && svg.is_none() && html.is_none() {
+                print!("{}", sweep::to_csv(&point_list));
+            }
+            if let Some(csv) = csv {
+                fs::write(csv, sweep::to_csv(&point_list))?;
+            }
+            if let Some(svg) = svg {
+                fs::write(svg, sweep::to_svg(&point_list))?;
+            }
+            if let Some(html) = html {
+                fs::write(html, sweep::to_html(&point_list))?;
+            }
+        }
+        Command::Generate {
+            out_dir,
+            seed,
+            members,
+            results,
+            in_race,
+            prob_nickname,
+            prob_typo,
+            prob_missing_city,
+            layout,
+        } => {
+            let generator = Generator {
+                seed,
+                member_count: members,
+                result_count: results,
+                members_in_race: in_race,
+                prob_nickname,
+                prob_typo,
+                prob_missing_city,
+                layout,
+            };
+            generator.generate()?.write(&out_dir)?;
+        }
+    }
+    Ok(())
+}
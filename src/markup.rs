@@ -0,0 +1,202 @@
+// A small, forgiving reader for XML (inside spreadsheet files) and saved HTML pages. It yields
+// start tags, end tags, and text in order, and never fails: anything it doesn't understand is
+// passed over. Text comes with entities such as "&amp;" decoded. Names are lowercased, and
+// namespace prefixes are kept, as in "table:table-cell".
+
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Event {
+    Start {
+        name: String,
+        attributes: HashMap<String, String>,
+        self_closing: bool,
+    },
+    End {
+        name: String,
+    },
+    Text(String),
+}
+
+pub(crate) struct Events<'a> {
+    rest: &'a str,
+    // Inside <script> or <style>, everything up to the end tag is text
+    raw_text_until: Option<&'static str>,
+}
+
+pub(crate) fn events(text: &str) -> Events<'_> {
+    Events {
+        rest: text,
+        raw_text_until: None,
+    }
+}
+
+impl Iterator for Events<'_> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            if let Some(end_tag) = self.raw_text_until.take() {
+                let end = find_ignore_case(self.rest, end_tag).unwrap_or(self.rest.len());
+                let (text, rest) = self.rest.split_at(end);
+                self.rest = rest;
+                if !text.is_empty() {
+                    return Some(Event::Text(text.to_string()));
+                }
+                continue;
+            }
+            if !self.rest.starts_with('<') {
+                let end = self.rest.find('<').unwrap_or(self.rest.len());
+                let (text, rest) = self.rest.split_at(end);
+                self.rest = rest;
+                return Some(Event::Text(decode_entities(text)));
+            }
+            if let Some(after) = self.rest.strip_prefix("<!--") {
+                self.rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+                continue;
+            }
+            if let Some(after) = self.rest.strip_prefix("<![CDATA[") {
+                let end = after.find("]]>").unwrap_or(after.len());
+                self.rest = after.get(end + 3..).unwrap_or("");
+                return Some(Event::Text(after[..end].to_string()));
+            }
+            // A '<' that never closes is just text
+            let Some(end) = tag_end(self.rest) else {
+                let text = self.rest;
+                self.rest = "";
+                return Some(Event::Text(decode_entities(text)));
+            };
+            let tag = &self.rest[1..end];
+            self.rest = self.rest.get(end + 1..).unwrap_or("");
+            if tag.starts_with(['!', '?']) {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                return Some(Event::End {
+                    name: name.trim().to_lowercase(),
+                });
+            }
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+            let name = tag[..name_end].to_lowercase();
+            if name.is_empty() {
+                continue;
+            }
+            if name == "script" || name == "style" {
+                self.raw_text_until = Some(if name == "script" {
+                    "</script"
+                } else {
+                    "</style"
+                });
+            }
+            return Some(Event::Start {
+                name,
+                attributes: parse_attributes(&tag[name_end..]),
+                self_closing,
+            });
+        }
+    }
+}
+
+// The '>' that ends the tag, skipping any inside quoted attribute values
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn find_ignore_case(text: &str, pattern: &str) -> Option<usize> {
+    text.char_indices().map(|(index, _)| index).find(|index| {
+        text.get(*index..index + pattern.len())
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(pattern))
+    })
+}
+
+fn parse_attributes(text: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_lowercase();
+        rest = rest[name_end..].trim_start();
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let end = inner.find(quote).unwrap_or(inner.len());
+                    (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    after.split_at(end)
+                }
+            };
+            value = decode_entities(raw);
+            rest = remaining.trim_start();
+        }
+        if !name.is_empty() {
+            attributes.insert(name, value);
+        }
+    }
+    attributes
+}
+
+// The XML entities, numeric references, and the few named HTML ones results pages use
+pub(crate) fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
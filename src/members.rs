@@ -0,0 +1,145 @@
+// Member lines are First,Last,City (separated by tab or comma), optionally followed by a
+// birth year, a gender, and a bib number (any of which may be blank). Instead, a first line such as
+// "First,Last,City,Gender" may name the columns, in any order.
+
+use itertools::Itertools;
+
+use crate::fields::Gender;
+use crate::is_comma_or_tab;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MemberColumn {
+    First,
+    Last,
+    City,
+    BirthYear,
+    Gender,
+    Bib,
+}
+
+const POSITIONAL_COLUMNS: [MemberColumn; 6] = [
+    MemberColumn::First,
+    MemberColumn::Last,
+    MemberColumn::City,
+    MemberColumn::BirthYear,
+    MemberColumn::Gender,
+    MemberColumn::Bib,
+];
+
+impl MemberColumn {
+    fn from_header(field: &str) -> Option<MemberColumn> {
+        match field.trim().to_lowercase().as_str() {
+            "first" | "first name" | "firstname" => Some(MemberColumn::First),
+            "last" | "last name" | "lastname" | "surname" => Some(MemberColumn::Last),
+            "city" | "hometown" | "town" => Some(MemberColumn::City),
+            "birth year" | "birthyear" | "born" | "yob" | "year of birth" => {
+                Some(MemberColumn::BirthYear)
+            }
+            "gender" | "sex" => Some(MemberColumn::Gender),
+            "bib" | "bib #" | "bib number" => Some(MemberColumn::Bib),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct MemberRecord<'a> {
+    pub first: &'a str,
+    pub last: &'a str,
+    pub city: &'a str,
+    pub birth_year: Option<u32>,
+    pub gender: Option<Gender>,
+    pub bib: Option<String>,
+}
+
+pub(crate) struct MemberColumns(Vec<MemberColumn>);
+
+impl MemberColumns {
+    // Every field must name a column, and there must be first and last name columns.
+    pub fn from_header(line: &str) -> Option<MemberColumns> {
+        let column_list = split_fields(line)
+            .into_iter()
+            .map(MemberColumn::from_header)
+            .collect::<Option<Vec<_>>>()?;
+        (column_list.contains(&MemberColumn::First) && column_list.contains(&MemberColumn::Last))
+            .then_some(MemberColumns(column_list))
+    }
+
+    pub fn parse<'a>(&self, line: &'a str) -> Result<MemberRecord<'a>, anyhow::Error> {
+        let fields = split_fields(line);
+        if fields.len() != self.0.len() {
+            anyhow::bail!(
+                "Line should have {} fields separated by tab or comma, not '{line}'",
+                self.0.len()
+            );
+        }
+        record(&self.0, &fields, line)
+    }
+}
+
+pub(crate) fn parse_positional(line: &str) -> Result<MemberRecord<'_>, anyhow::Error> {
+    let fields = split_fields(line);
+    if !(3..=POSITIONAL_COLUMNS.len()).contains(&fields.len()) {
+        anyhow::bail!("Line should be First,Last,City separated by tab or comma, not '{line}'");
+    }
+    record(&POSITIONAL_COLUMNS, &fields, line)
+}
+
+// A tab-separated line (say, from a spreadsheet) may have commas inside fields, as in
+// "Portland, OR"
+fn split_fields(line: &str) -> Vec<&str> {
+    if line.contains('\t') {
+        line.split('\t').collect_vec()
+    } else {
+        line.split(is_comma_or_tab).collect_vec()
+    }
+}
+
+fn record<'a>(
+    column_list: &[MemberColumn],
+    fields: &[&'a str],
+    line: &str,
+) -> Result<MemberRecord<'a>, anyhow::Error> {
+    let mut record = MemberRecord::default();
+    for (column, field) in column_list.iter().zip(fields) {
+        match column {
+            MemberColumn::First => record.first = field,
+            MemberColumn::Last => record.last = field,
+            MemberColumn::City => record.city = field,
+            MemberColumn::BirthYear => {
+                let field = field.trim();
+                if !field.is_empty() {
+                    let Ok(birth_year) = field.parse::<u32>() else {
+                        anyhow::bail!("Birth year should be a number, not '{field}' in '{line}'");
+                    };
+                    record.birth_year = Some(birth_year);
+                }
+            }
+            MemberColumn::Gender => {
+                let field = field.trim();
+                if !field.is_empty() {
+                    let Some(gender) = Gender::parse(field) else {
+                        anyhow::bail!("Gender should be F or M, not '{field}' in '{line}'");
+                    };
+                    record.gender = Some(gender);
+                }
+            }
+            MemberColumn::Bib => {
+                let field = field.trim();
+                if !field.is_empty() {
+                    record.bib = Some(normalize_bib(field));
+                }
+            }
+        }
+    }
+    Ok(record)
+}
+
+// Bibs compare without case or leading zeros, so "0317" is "317"
+pub(crate) fn normalize_bib(bib: &str) -> String {
+    let bib = bib.trim().to_uppercase();
+    match bib.trim_start_matches('0') {
+        "" => bib,
+        trimmed => trimmed.to_string(),
+    }
+}
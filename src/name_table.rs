@@ -0,0 +1,181 @@
+// The bundled name table (data/name_probability.tsv), built into a compact binary form by
+// build.rs. It's deflated in the program and inflated once, on first use, then searched in
+// place, so nothing is parsed at startup.
+//
+// Names are sorted and stored in blocks of BLOCK_LEN. Each block's first name is stored whole,
+// and each name after it as the length of the prefix it shares with the name before, plus the
+// rest. Probabilities are log-probabilities rounded to 16-bit steps. All numbers are
+// little-endian:
+//
+//   name count: u32, block count: u32, smallest probability: f32,
+//   smallest log-probability: f32, log-probability step: f32,
+//   each block's offset into the entries: u32 per block,
+//   each name's probability step count: u16 per name,
+//   entries: (lengths, suffix) per name
+//
+// where lengths is one byte, the shared prefix length times 16 plus the suffix length, when the
+// prefix is under 15 and the suffix under 16, and otherwise 0xFF followed by a byte for each.
+//
+// The step counts are kept apart from the names because they compress poorly and would get in
+// the way of the names compressing well.
+//
+// A lookup binary searches the blocks by their first names, then reads at most one block.
+
+use core::cmp::Ordering;
+use std::io::Read;
+use std::sync::OnceLock;
+
+// Must match build.rs
+const BLOCK_LEN: usize = 16;
+const HEADER_LEN: usize = 20;
+const MAX_NAME_LEN: usize = u8::MAX as usize;
+
+static BUNDLED_DEFLATED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/name_table.deflate"));
+
+#[derive(Clone, Copy)]
+pub(crate) struct NameTable {
+    bytes: &'static [u8],
+}
+
+impl NameTable {
+    pub fn bundled() -> Self {
+        static BUNDLED: OnceLock<Vec<u8>> = OnceLock::new();
+        let bytes = BUNDLED.get_or_init(|| {
+            let mut bytes = Vec::new();
+            libflate::deflate::Decoder::new(BUNDLED_DEFLATED)
+                .read_to_end(&mut bytes)
+                .expect("the bundled name table is corrupt");
+            bytes
+        });
+        Self { bytes }
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        u32::from_le_bytes(self.bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn f32_at(&self, offset: usize) -> f32 {
+        f32::from_le_bytes(self.bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.u32_at(0) as usize
+    }
+
+    fn block_count(&self) -> usize {
+        self.u32_at(4) as usize
+    }
+
+    // The smallest probability in the table
+    pub fn min_prob(&self) -> f32 {
+        self.f32_at(8)
+    }
+
+    // The probability of the name at `index`
+    fn prob_at(&self, index: usize) -> f32 {
+        let offset = HEADER_LEN + 4 * self.block_count() + 2 * index;
+        let code = u16::from_le_bytes([self.bytes[offset], self.bytes[offset + 1]]);
+        (self.f32_at(12) + code as f32 * self.f32_at(16)).exp()
+    }
+
+    fn entries(&self) -> &'static [u8] {
+        &self.bytes[HEADER_LEN + 4 * self.block_count() + 2 * self.len()..]
+    }
+
+    fn block_start(&self, block_index: usize) -> usize {
+        self.u32_at(HEADER_LEN + 4 * block_index) as usize
+    }
+
+    // The first name of a block, which is stored whole
+    fn block_first_name(&self, block_index: usize) -> &'static [u8] {
+        let entries = self.entries();
+        let (_, suffix_len, suffix_start) = read_lengths(entries, self.block_start(block_index));
+        &entries[suffix_start..suffix_start + suffix_len]
+    }
+
+    pub fn prob(&self, name: &str) -> Option<f32> {
+        let name = name.as_bytes();
+        // The last block whose first name is at or before `name`
+        let (mut low, mut high) = (0, self.block_count());
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.block_first_name(middle) <= name {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let block_index = low.checked_sub(1)?;
+        let mut reader = EntryReader::new(self.entries(), self.block_start(block_index));
+        let block_first = block_index * BLOCK_LEN;
+        for index in block_first..self.len().min(block_first + BLOCK_LEN) {
+            reader.next_entry();
+            match reader.name().cmp(name) {
+                Ordering::Less => continue,
+                Ordering::Equal => return Some(self.prob_at(index)),
+                Ordering::Greater => return None,
+            }
+        }
+        None
+    }
+
+    // Every name, in order, with its probability
+    pub fn iter(&self) -> impl Iterator<Item = (String, f32)> + '_ {
+        let mut reader = EntryReader::new(self.entries(), 0);
+        (0..self.len()).map(move |index| {
+            reader.next_entry();
+            // Names are A-Z, so always UTF-8
+            let name = String::from_utf8_lossy(reader.name()).into_owned();
+            (name, self.prob_at(index))
+        })
+    }
+}
+
+// An entry's shared prefix length and suffix length, and where its suffix starts
+fn read_lengths(entries: &[u8], offset: usize) -> (usize, usize, usize) {
+    match entries[offset] {
+        0xFF => (
+            entries[offset + 1] as usize,
+            entries[offset + 2] as usize,
+            offset + 3,
+        ),
+        lengths => (
+            (lengths >> 4) as usize,
+            (lengths & 0x0F) as usize,
+            offset + 1,
+        ),
+    }
+}
+
+// Reads entries in order, rebuilding each name from the one before
+struct EntryReader {
+    entries: &'static [u8],
+    offset: usize,
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+}
+
+impl EntryReader {
+    fn new(entries: &'static [u8], offset: usize) -> Self {
+        Self {
+            entries,
+            offset,
+            name: [0; MAX_NAME_LEN],
+            name_len: 0,
+        }
+    }
+
+    // Moves to the next entry
+    fn next_entry(&mut self) {
+        let entries = self.entries;
+        let (shared, suffix_len, suffix_start) = read_lengths(entries, self.offset);
+        self.name_len = shared + suffix_len;
+        self.name[shared..self.name_len]
+            .copy_from_slice(&entries[suffix_start..suffix_start + suffix_len]);
+        self.offset = suffix_start + suffix_len;
+    }
+
+    fn name(&self) -> &[u8] {
+        &self.name[..self.name_len]
+    }
+}
@@ -0,0 +1,475 @@
+// Results posted as PDF (with the "pdf" feature). A PDF page has no lines of text, only runs of
+// text drawn at positions. The runs are grouped into lines by their baseline, top to bottom, and
+// each run is placed on a grid of character columns, so that a table's columns in the PDF become
+// space-aligned columns in the text, which `layout` then finds. Text is decoded with the font's
+// ToUnicode map when it has one, and with its simple encoding otherwise. Scanned pages (images of
+// text) have no text to extract.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use itertools::Itertools;
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object};
+
+// A transformation matrix [a b c d e f], as PDF writes them
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// Glyph width, in thousandths of the font size, when the font doesn't say
+const DEFAULT_WIDTH: f32 = 500.0;
+
+// Runs closer than this (in character widths) are parts of one word
+const JOIN_GAP: f32 = 0.25;
+
+// Text drawn in one place, in page space
+struct Run {
+    x: f32,
+    y: f32,
+    end_x: f32,
+    size: f32,
+    text: String,
+}
+
+struct Font {
+    encoding: String,
+    // Bytes per character code: 2 for composite (Type0) fonts, else 1
+    code_len: usize,
+    to_unicode: Option<HashMap<u32, String>>,
+    width_map: HashMap<u32, f32>,
+    default_width: f32,
+}
+
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+fn translate(tx: f32, ty: f32, m: &Matrix) -> Matrix {
+    multiply(&[1.0, 0.0, 0.0, 1.0, tx, ty], m)
+}
+
+fn numbers(operands: &[Object]) -> Vec<f32> {
+    operands
+        .iter()
+        .filter_map(|operand| operand.as_float().ok())
+        .collect()
+}
+
+fn as_matrix(operands: &[Object]) -> Option<Matrix> {
+    let number_list = numbers(operands);
+    (number_list.len() == 6).then(|| {
+        [
+            number_list[0],
+            number_list[1],
+            number_list[2],
+            number_list[3],
+            number_list[4],
+            number_list[5],
+        ]
+    })
+}
+
+fn code_list(bytes: &[u8], code_len: usize) -> impl Iterator<Item = u32> + '_ {
+    bytes.chunks(code_len).map(|chunk| {
+        chunk
+            .iter()
+            .fold(0, |code, byte| (code << 8) | *byte as u32)
+    })
+}
+
+impl Font {
+    fn new(document: &Document, dictionary: &Dictionary) -> Self {
+        let subtype = dictionary
+            .get(b"Subtype")
+            .and_then(Object::as_name_str)
+            .unwrap_or("");
+        let code_len = if subtype == "Type0" { 2 } else { 1 };
+        let to_unicode = dictionary
+            .get_deref(b"ToUnicode", document)
+            .and_then(Object::as_stream)
+            .ok()
+            .map(|stream| {
+                let bytes = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                parse_to_unicode(&String::from_utf8_lossy(&bytes))
+            });
+
+        let mut width_map = HashMap::new();
+        let mut default_width = DEFAULT_WIDTH;
+        if code_len == 1 {
+            let first_char = dictionary
+                .get(b"FirstChar")
+                .and_then(Object::as_i64)
+                .unwrap_or(0);
+            if let Ok(width_list) = dictionary
+                .get_deref(b"Widths", document)
+                .and_then(Object::as_array)
+            {
+                for (index, width) in width_list.iter().enumerate() {
+                    if let Ok(width) = document.dereference(width).and_then(|(_, w)| w.as_float()) {
+                        width_map.insert((first_char + index as i64) as u32, width);
+                    }
+                }
+            }
+        } else if let Some(descendant) = dictionary
+            .get_deref(b"DescendantFonts", document)
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|font_list| font_list.first())
+            .and_then(|font| document.dereference(font).ok())
+            .and_then(|(_, font)| font.as_dict().ok())
+        {
+            default_width = descendant
+                .get(b"DW")
+                .and_then(Object::as_float)
+                .unwrap_or(1000.0);
+            if let Ok(w) = descendant
+                .get_deref(b"W", document)
+                .and_then(Object::as_array)
+            {
+                parse_cid_widths(w, &mut width_map);
+            }
+        }
+
+        Self {
+            encoding: dictionary.get_font_encoding().to_string(),
+            code_len,
+            to_unicode,
+            width_map,
+            default_width,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        match &self.to_unicode {
+            Some(to_unicode) => code_list(bytes, self.code_len)
+                .map(|code| match to_unicode.get(&code) {
+                    Some(text) => text.clone(),
+                    None if self.code_len == 1 => {
+                        Document::decode_text(Some(&self.encoding), &[code as u8])
+                    }
+                    None => String::new(),
+                })
+                .collect(),
+            None => Document::decode_text(Some(&self.encoding), bytes),
+        }
+    }
+
+    // In thousandths of the font size
+    fn width(&self, bytes: &[u8]) -> f32 {
+        code_list(bytes, self.code_len)
+            .map(|code| {
+                self.width_map
+                    .get(&code)
+                    .copied()
+                    .unwrap_or(self.default_width)
+            })
+            .sum()
+    }
+}
+
+// A composite font's widths: "c [w1 w2 ...]" gives widths from code c on, and "first last w"
+// gives one width to a range of codes
+fn parse_cid_widths(w: &[Object], width_map: &mut HashMap<u32, f32>) {
+    let mut index = 0;
+    while index + 1 < w.len() {
+        let Ok(first) = w[index].as_i64() else {
+            return;
+        };
+        if let Ok(width_list) = w[index + 1].as_array() {
+            for (offset, width) in numbers(width_list).into_iter().enumerate() {
+                width_map.insert(first as u32 + offset as u32, width);
+            }
+            index += 2;
+        } else {
+            let (Some(last), Some(width)) = (
+                w[index + 1].as_i64().ok(),
+                w.get(index + 2).and_then(|width| width.as_float().ok()),
+            ) else {
+                return;
+            };
+            for code in first..=last.min(first + u16::MAX as i64) {
+                width_map.insert(code as u32, width);
+            }
+            index += 3;
+        }
+    }
+}
+
+fn hex_value(token: &str) -> Option<Vec<u8>> {
+    let hex = token.strip_prefix('<')?.strip_suffix('>')?;
+    let hex = hex.chars().filter(|c| !c.is_whitespace()).collect_vec();
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok())
+        .collect()
+}
+
+fn hex_code(token: &str) -> Option<u32> {
+    Some(
+        hex_value(token)?
+            .iter()
+            .fold(0, |code, byte| (code << 8) | *byte as u32),
+    )
+}
+
+fn utf16_text(bytes: &[u8]) -> String {
+    let unit_list = bytes
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+        .collect_vec();
+    String::from_utf16_lossy(&unit_list)
+}
+
+// The "bfchar" and "bfrange" entries of a ToUnicode CMap, which map character codes to UTF-16BE
+fn parse_to_unicode(cmap: &str) -> HashMap<u32, String> {
+    let spaced = cmap
+        .replace('[', " [ ")
+        .replace(']', " ] ")
+        .replace('<', " <")
+        .replace('>', "> ");
+    let token_list = spaced.split_whitespace().collect_vec();
+    let mut to_unicode = HashMap::new();
+    let mut index = 0;
+    while index < token_list.len() {
+        match token_list[index] {
+            "beginbfchar" => {
+                index += 1;
+                while index + 1 < token_list.len() && token_list[index] != "endbfchar" {
+                    if let (Some(code), Some(text)) = (
+                        hex_code(token_list[index]),
+                        hex_value(token_list[index + 1]),
+                    ) {
+                        to_unicode.insert(code, utf16_text(&text));
+                    }
+                    index += 2;
+                }
+            }
+            "beginbfrange" => {
+                index += 1;
+                while index + 2 < token_list.len() && token_list[index] != "endbfrange" {
+                    let (Some(low), Some(high)) =
+                        (hex_code(token_list[index]), hex_code(token_list[index + 1]))
+                    else {
+                        break;
+                    };
+                    index += 2;
+                    if token_list[index] == "[" {
+                        index += 1;
+                        let mut code = low;
+                        while index < token_list.len() && token_list[index] != "]" {
+                            if let Some(text) = hex_value(token_list[index]) {
+                                to_unicode.insert(code, utf16_text(&text));
+                            }
+                            code += 1;
+                            index += 1;
+                        }
+                        index += 1;
+                    } else {
+                        if let Some(mut text) = hex_value(token_list[index]) {
+                            // Later codes count up from the first one's last unit
+                            for code in low..=high.min(low + u16::MAX as u32) {
+                                to_unicode.insert(code, utf16_text(&text));
+                                if let Some(last) = text.last_mut() {
+                                    *last = last.wrapping_add(1);
+                                }
+                            }
+                        }
+                        index += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    to_unicode
+}
+
+// The text runs of one page, following the text state through the page's content
+fn page_runs(document: &Document, page_id: lopdf::ObjectId) -> Result<Vec<Run>, anyhow::Error> {
+    let font_map: HashMap<Vec<u8>, Font> = document
+        .get_page_fonts(page_id)
+        .into_iter()
+        .map(|(name, dictionary)| (name, Font::new(document, dictionary)))
+        .collect();
+    let content = Content::decode(&document.get_page_content(page_id)?)?;
+
+    let mut run_list = Vec::new();
+    let mut ctm = IDENTITY;
+    let mut ctm_stack = Vec::new();
+    let mut text_matrix = IDENTITY;
+    let mut line_matrix = IDENTITY;
+    let mut leading = 0.0;
+    let mut font: Option<&Font> = None;
+    let mut font_size = 0.0;
+    for operation in &content.operations {
+        let operands = &operation.operands;
+        let mut shown = Vec::new();
+        match operation.operator.as_str() {
+            "q" => ctm_stack.push(ctm),
+            "Q" => ctm = ctm_stack.pop().unwrap_or(IDENTITY),
+            "cm" => {
+                if let Some(matrix) = as_matrix(operands) {
+                    ctm = multiply(&matrix, &ctm);
+                }
+            }
+            "BT" => {
+                text_matrix = IDENTITY;
+                line_matrix = IDENTITY;
+            }
+            "Tf" => {
+                font = operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| font_map.get(name));
+                font_size = operands
+                    .get(1)
+                    .and_then(|size| size.as_float().ok())
+                    .unwrap_or(0.0);
+            }
+            "TL" => leading = numbers(operands).first().copied().unwrap_or(0.0),
+            "Tm" => {
+                if let Some(matrix) = as_matrix(operands) {
+                    text_matrix = matrix;
+                    line_matrix = matrix;
+                }
+            }
+            operator @ ("Td" | "TD") => {
+                if let [tx, ty] = numbers(operands)[..] {
+                    if operator == "TD" {
+                        leading = -ty;
+                    }
+                    line_matrix = translate(tx, ty, &line_matrix);
+                    text_matrix = line_matrix;
+                }
+            }
+            "T*" => {
+                line_matrix = translate(0.0, -leading, &line_matrix);
+                text_matrix = line_matrix;
+            }
+            "Tj" => shown.extend(operands.first()),
+            "TJ" => {
+                if let Some(Ok(part_list)) = operands.first().map(Object::as_array) {
+                    shown.extend(part_list);
+                }
+            }
+            "'" | "\"" => {
+                line_matrix = translate(0.0, -leading, &line_matrix);
+                text_matrix = line_matrix;
+                shown.extend(operands.last());
+            }
+            _ => {}
+        }
+        let Some(font) = font else {
+            continue;
+        };
+        for part in shown {
+            match part {
+                Object::String(bytes, _) => {
+                    let advance = font.width(bytes) / 1000.0 * font_size;
+                    let start = multiply(&text_matrix, &ctm);
+                    text_matrix = translate(advance, 0.0, &text_matrix);
+                    let end = multiply(&text_matrix, &ctm);
+                    let text = font.decode(bytes);
+                    if !text.trim().is_empty() {
+                        run_list.push(Run {
+                            x: start[4],
+                            y: start[5],
+                            end_x: end[4],
+                            size: font_size * f32::hypot(start[2], start[3]),
+                            text,
+                        });
+                    }
+                }
+                // An adjustment, in thousandths of the font size, moving the next text left
+                adjustment => {
+                    if let Ok(adjustment) = adjustment.as_float() {
+                        text_matrix =
+                            translate(-adjustment / 1000.0 * font_size, 0.0, &text_matrix);
+                    }
+                }
+            }
+        }
+    }
+    Ok(run_list)
+}
+
+// Runs sharing a baseline (within half the text size), top of the page first, each left to right
+fn group_lines(mut run_list: Vec<Run>) -> Vec<Vec<Run>> {
+    run_list.sort_by(|a, b| b.y.total_cmp(&a.y));
+    let mut line_list: Vec<Vec<Run>> = Vec::new();
+    for run in run_list {
+        match line_list.last_mut() {
+            Some(line) if (line[0].y - run.y).abs() <= line[0].size.min(run.size) / 2.0 => {
+                line.push(run)
+            }
+            _ => line_list.push(vec![run]),
+        }
+    }
+    for line in &mut line_list {
+        line.sort_by(|a, b| a.x.total_cmp(&b.x));
+    }
+    line_list
+}
+
+// Each run starts at the character column nearest its position, with at least a space after the
+// run before, unless the two touch
+fn place_runs(line: &[Run], left: f32, char_width: f32) -> String {
+    let mut text = String::new();
+    let mut previous_end = None;
+    for run in line {
+        let column = ((run.x - left) / char_width).round().max(0.0) as usize;
+        let length = text.chars().count();
+        match previous_end {
+            Some(end) if run.x - end < JOIN_GAP * char_width => {}
+            Some(_) => {
+                let padding = column.saturating_sub(length).max(1);
+                text.extend(core::iter::repeat_n(' ', padding));
+            }
+            None => text.extend(core::iter::repeat_n(' ', column)),
+        }
+        text.push_str(&run.text);
+        previous_end = Some(run.end_x);
+    }
+    text.trim_end().to_string()
+}
+
+pub fn to_result_lines(bytes: &[u8]) -> Result<Vec<String>, anyhow::Error> {
+    let document = Document::load_mem(bytes).context("Not a readable PDF")?;
+    let line_list = document
+        .page_iter()
+        .map(|page_id| Ok(group_lines(page_runs(&document, page_id)?)))
+        .collect::<Result<Vec<_>, anyhow::Error>>()?
+        .into_iter()
+        .flatten()
+        .collect_vec();
+    if line_list.is_empty() {
+        anyhow::bail!("PDF has no text (it may be a scanned image)");
+    }
+
+    // One grid for every page, so that columns line up across page breaks
+    let run_iter = || line_list.iter().flatten();
+    let left = run_iter().map(|run| run.x).fold(f32::INFINITY, f32::min);
+    let char_count: usize = run_iter().map(|run| run.text.chars().count()).sum();
+    let total_width: f32 = run_iter().map(|run| (run.end_x - run.x).abs()).sum();
+    let char_width = if total_width > 0.0 {
+        total_width / char_count as f32
+    } else {
+        DEFAULT_WIDTH / 1000.0 * run_iter().map(|run| run.size).fold(0.0, f32::max)
+    }
+    .max(f32::EPSILON);
+
+    Ok(line_list
+        .iter()
+        .map(|line| place_runs(line, left, char_width))
+        .collect())
+}
@@ -0,0 +1,145 @@
+// Copy-pasting results from a web page often splits one result across several lines, such as
+// place, name, hometown, and time each on its own line. Before tokenizing, such records are
+// joined back into one line each, with tabs between the parts.
+//
+// Records are found by their places: a line that starts with the place after the previous
+// record's place starts a record. When records usually span the same number of lines, each
+// record is the lines up to the next start, but no more than that usual number, so headings
+// between sections stay on their own lines. A new section starts back at place 1, but only a
+// full record's length after the previous start, so that a bib of 1 isn't taken for a place.
+// When most records are one line, nothing is joined.
+
+use itertools::Itertools;
+
+const MIN_RECORDS: usize = 3;
+
+pub(crate) struct Records {
+    pub line_list: Vec<String>,
+    // For each record, the index of its first result line
+    pub start_list: Vec<usize>,
+}
+
+impl Records {
+    // Each line is its own record
+    fn unjoined(result_lines: &[String]) -> Records {
+        Records {
+            line_list: result_lines.to_vec(),
+            start_list: (0..result_lines.len()).collect(),
+        }
+    }
+}
+
+// `lines_per_record` of `None` detects records; `Some(n)` joins every n lines.
+pub(crate) fn segment(result_lines: &[String], lines_per_record: Option<usize>) -> Records {
+    let lines_per_record = match lines_per_record {
+        Some(lines_per_record) => lines_per_record,
+        None => match detect_lines_per_record(result_lines) {
+            Some(lines_per_record) => {
+                let start_list = record_starts(result_lines, Some(lines_per_record));
+                return join_records(result_lines, &start_list, lines_per_record);
+            }
+            None => 1,
+        },
+    };
+    if lines_per_record <= 1 {
+        return Records::unjoined(result_lines);
+    }
+    let start_list = (0..result_lines.len())
+        .step_by(lines_per_record)
+        .collect_vec();
+    join_records(result_lines, &start_list, lines_per_record)
+}
+
+// "12", "12.", or "12)" at the start of a line
+pub(crate) fn leading_place(line: &str) -> Option<usize> {
+    line.split_whitespace()
+        .next()?
+        .trim_end_matches(['.', ')'])
+        .parse::<usize>()
+        .ok()
+}
+
+// With `lines_per_record`, place 1 may also start a new section.
+fn record_starts(result_lines: &[String], lines_per_record: Option<usize>) -> Vec<usize> {
+    let mut start_list: Vec<usize> = Vec::new();
+    let mut previous_place = None;
+    for (line_index, line) in result_lines.iter().enumerate() {
+        let Some(place) = leading_place(line) else {
+            continue;
+        };
+        let is_restart = match (lines_per_record, start_list.last()) {
+            (Some(lines_per_record), Some(previous_start)) => {
+                place == 1 && line_index - previous_start >= lines_per_record
+            }
+            _ => false,
+        };
+        let is_start = match previous_place {
+            None => true,
+            Some(previous_place) => place == previous_place + 1 || is_restart,
+        };
+        if is_start {
+            start_list.push(line_index);
+            previous_place = Some(place);
+        }
+    }
+    start_list
+}
+
+// The most common distance between record starts, if that is more than one line and at least
+// half the records have it
+fn detect_lines_per_record(result_lines: &[String]) -> Option<usize> {
+    let start_list = record_starts(result_lines, None);
+    if start_list.len() < MIN_RECORDS {
+        return None;
+    }
+    let gap_list = start_list
+        .iter()
+        .tuple_windows()
+        .map(|(start, next)| next - start)
+        .collect_vec();
+    let (lines_per_record, count) = gap_list
+        .iter()
+        .counts()
+        .into_iter()
+        .max_by_key(|(gap, count)| (*count, usize::MAX - **gap))?;
+    (*lines_per_record > 1 && count * 2 >= gap_list.len()).then_some(*lines_per_record)
+}
+
+fn join_records(result_lines: &[String], start_list: &[usize], lines_per_record: usize) -> Records {
+    let mut records = Records {
+        line_list: Vec::new(),
+        start_list: Vec::new(),
+    };
+    let mut line_index = 0;
+    for (index, start) in start_list.iter().enumerate() {
+        // Lines before this record (titles, headings) stay as they are
+        for (before, line) in result_lines
+            .iter()
+            .enumerate()
+            .take(*start)
+            .skip(line_index)
+        {
+            records.line_list.push(line.clone());
+            records.start_list.push(before);
+        }
+        let next = start_list
+            .get(index + 1)
+            .copied()
+            .unwrap_or(result_lines.len());
+        let end = next.min(start + lines_per_record);
+        records.line_list.push(
+            result_lines[*start..end]
+                .iter()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .join("\t"),
+        );
+        records.start_list.push(*start);
+        line_index = end;
+    }
+    for (after, line) in result_lines.iter().enumerate().skip(line_index) {
+        records.line_list.push(line.clone());
+        records.start_list.push(after);
+    }
+    records
+}
@@ -0,0 +1,297 @@
+// The matches as data (for JSON) and as plain text, grouped either by result line (like the
+// HTML output) or by member (who probably raced, and on which lines).
+
+use core::str::FromStr;
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::fields::ResultFields;
+use crate::LinePeople;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    Line,
+    Member,
+}
+
+impl FromStr for GroupBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "line" => Ok(GroupBy::Line),
+            "member" => Ok(GroupBy::Member),
+            _ => anyhow::bail!("Group by should be 'line' or 'member', not '{s}'"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Html,
+    Json,
+    Text,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "html" => Ok(OutputFormat::Html),
+            "json" => Ok(OutputFormat::Json),
+            "text" => Ok(OutputFormat::Text),
+            _ => anyhow::bail!("Output format should be 'html', 'json', or 'text', not '{s}'"),
+        }
+    }
+}
+
+// Line and member numbers are 1-based, as in the input files.
+#[derive(Serialize, Clone, Debug)]
+pub struct LineReport {
+    pub line_number: usize,
+    pub line: String,
+    pub max_prob: f32,
+    pub fields: ResultFields,
+    pub members: Vec<MemberCandidate>,
+}
+
+// For printing one line at a time, as streaming does
+impl LineReport {
+    pub fn to_text(&self) -> Vec<String> {
+        lines_to_text(core::slice::from_ref(self))
+    }
+
+    // One line of JSON Lines
+    pub fn to_json_line(&self) -> String {
+        serde_json::to_string(self).unwrap() // only plain data, so can't fail
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MemberCandidate {
+    pub member_number: usize,
+    pub member: String,
+    pub prob: f32,
+    pub points: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MemberReport {
+    pub member_number: usize,
+    pub member: String,
+    pub max_prob: f32,
+    // More than one line is plausible, so someone should look.
+    pub needs_review: bool,
+    pub lines: Vec<LineCandidate>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LineCandidate {
+    pub line_number: usize,
+    pub line: String,
+    pub fields: ResultFields,
+    pub prob: f32,
+    pub points: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_prob_member_in_race: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<&'a [LineReport]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    members: Option<&'a [MemberReport]>,
+}
+
+// Keeps the order of `line_people_list` (most likely line first)
+pub(crate) fn line_reports(line_people_list: &[LinePeople]) -> Vec<LineReport> {
+    line_people_list
+        .iter()
+        .map(|line_people| LineReport {
+            line_number: line_people.line_index + 1,
+            line: line_people.line.clone(),
+            max_prob: line_people.max_prob,
+            fields: line_people.fields.clone(),
+            members: line_people
+                .person_prob_list
+                .iter()
+                .sorted_by(|a, b| b.prob.partial_cmp(&a.prob).unwrap())
+                .map(|person_prob| MemberCandidate {
+                    member_number: person_prob.person.id + 1,
+                    member: person_prob.person.input_pretty.clone(),
+                    prob: person_prob.prob,
+                    points: person_prob.delta,
+                    flags: person_prob.flags.clone(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+// Most likely member first, each with their lines from most to least likely.
+pub(crate) fn member_reports(
+    line_people_list: &[LinePeople],
+    review_probability: f32,
+) -> Vec<MemberReport> {
+    let mut id_to_report = HashMap::<usize, MemberReport>::new();
+    for line_people in line_people_list {
+        for person_prob in line_people.person_prob_list.iter() {
+            let person = &person_prob.person;
+            let report = id_to_report
+                .entry(person.id)
+                .or_insert_with(|| MemberReport {
+                    member_number: person.id + 1,
+                    member: person.input_pretty.clone(),
+                    max_prob: 0.0,
+                    needs_review: false,
+                    lines: Vec::new(),
+                });
+            report.max_prob = report.max_prob.max(person_prob.prob);
+            report.lines.push(LineCandidate {
+                line_number: line_people.line_index + 1,
+                line: line_people.line.clone(),
+                fields: line_people.fields.clone(),
+                prob: person_prob.prob,
+                points: person_prob.delta,
+                flags: person_prob.flags.clone(),
+            });
+        }
+    }
+    let mut report_list = id_to_report.into_values().collect_vec();
+    for report in report_list.iter_mut() {
+        report.lines.sort_by(|a, b| {
+            b.prob
+                .partial_cmp(&a.prob)
+                .unwrap()
+                .then(a.line_number.cmp(&b.line_number))
+        });
+        report.needs_review = report
+            .lines
+            .iter()
+            .filter(|line| line.prob >= review_probability)
+            .count()
+            > 1;
+    }
+    report_list.sort_by(|a, b| {
+        b.max_prob
+            .partial_cmp(&a.max_prob)
+            .unwrap()
+            .then(a.member_number.cmp(&b.member_number))
+    });
+    report_list
+}
+
+pub(crate) fn to_json(
+    estimated_prob_member_in_race: Option<f32>,
+    line_report_list: Option<&[LineReport]>,
+    member_report_list: Option<&[MemberReport]>,
+) -> String {
+    let json_report = JsonReport {
+        estimated_prob_member_in_race,
+        lines: line_report_list,
+        members: member_report_list,
+    };
+    serde_json::to_string_pretty(&json_report).unwrap() // only plain data, so can't fail
+}
+
+pub(crate) fn lines_to_text(line_report_list: &[LineReport]) -> Vec<String> {
+    let mut text_list = Vec::new();
+    for line_report in line_report_list {
+        text_list.push(format!("{}: {}", line_report.line_number, line_report.line));
+        for candidate in line_report.members.iter() {
+            text_list.push(format!(
+                "\t{:>3.0}%\t{}\t{:.2} pts{}",
+                candidate.prob * 100.0,
+                candidate.member,
+                candidate.points,
+                flags_to_text(&candidate.flags)
+            ));
+        }
+    }
+    text_list
+}
+
+pub(crate) fn members_to_text(member_report_list: &[MemberReport]) -> Vec<String> {
+    let mut text_list = Vec::new();
+    for member_report in member_report_list {
+        let mut header = member_report.member.clone();
+        if member_report.needs_review {
+            header.push_str("\t[review: more than one plausible line]");
+        }
+        text_list.push(header);
+        for candidate in member_report.lines.iter() {
+            text_list.push(format!(
+                "\t{:>3.0}%\t{}: {}\t{:.2} pts{}",
+                candidate.prob * 100.0,
+                candidate.line_number,
+                candidate.line,
+                candidate.points,
+                flags_to_text(&candidate.flags)
+            ));
+        }
+    }
+    text_list
+}
+
+// Names, cities, and result lines come from input files, so they may hold '<' or '&'. Only
+// used for text between tags, never in attributes.
+pub(crate) fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn flags_to_text(flags: &[String]) -> String {
+    flags.iter().map(|flag| format!("\t[{flag}]")).collect()
+}
+
+pub(crate) fn members_to_html(member_report_list: &[MemberReport]) -> Vec<String> {
+    let mut html_list = Vec::new();
+    for member_report in member_report_list {
+        let mut header = format!("<pre>{}", escape_html(&member_report.member));
+        if member_report.needs_review {
+            header.push_str(
+                " <mark style=\"background-color: orange;\">review: more than one plausible line</mark>",
+            );
+        }
+        header.push_str("</pre>");
+        html_list.push(header);
+        html_list.push("<div class=\"indented-table\"><table>".to_string());
+        for candidate in member_report.lines.iter() {
+            html_list.push(format!(
+                "
+                    <tr>
+                    <td class=\"numeric\">{prob:.0}%</td>
+                    <td class=\"text\">{line_number}: {line}{flags}</td>
+                    <td class=\"numeric\">{points:.2} pts</td>
+                </tr>",
+                prob = candidate.prob * 100.0,
+                line_number = candidate.line_number,
+                line = escape_html(&candidate.line),
+                flags = candidate
+                    .flags
+                    .iter()
+                    .map(|flag| format!(" <mark style=\"background-color: orange;\">{flag}</mark>"))
+                    .join(""),
+                points = candidate.points
+            ));
+        }
+        html_list.push("</table></div>".to_string());
+    }
+    html_list
+}
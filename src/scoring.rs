@@ -0,0 +1,377 @@
+// Club points and team scores from a season of matched races (see `season`), using the
+// place, time, and gender parsed from each matched line (see `fields`).
+//
+// In each race, members are ranked among themselves, separately by gender, by overall place
+// (or by time when the results have no places). The club's first finisher gets the first
+// entry of the points table, and so on; everyone after the table gets the participation
+// points. A team score is the sum of the overall places of the club's top `team_size`
+// finishers of one gender, as in cross-country, so lower is better.
+
+use core::fmt::Write;
+use core::str::FromStr;
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::fields::{format_time, Gender};
+use crate::report::escape_html;
+use crate::season::{Race, SeasonReport};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointsTable(pub Vec<u32>);
+
+impl Default for PointsTable {
+    fn default() -> Self {
+        Self(vec![10, 8, 6, 5, 4, 3, 2, 1])
+    }
+}
+
+// Comma-separated points for 1st, 2nd, ... such as "10,8,6,5,4,3,2,1"
+impl FromStr for PointsTable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let points_list = s
+            .split(',')
+            .map(|points| points.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| {
+                anyhow::anyhow!("Points table should be numbers separated by commas, not '{s}'")
+            })?;
+        Ok(Self(points_list))
+    }
+}
+
+pub struct Scoring {
+    pub points_table: PointsTable,
+    pub participation_points: u32,
+    pub team_size: usize,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self {
+            points_table: PointsTable::default(),
+            participation_points: 1,
+            team_size: 3,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScoredFinisher {
+    pub member_number: usize,
+    pub member: String,
+    pub gender: Option<Gender>,
+    pub place: Option<usize>,
+    // seconds
+    pub time: Option<f32>,
+    // 1-based, among club members of the same gender
+    pub club_place: usize,
+    pub points: u32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TeamScore {
+    pub gender: Option<Gender>,
+    pub score: usize,
+    pub member_numbers: Vec<usize>,
+    // false when fewer than `team_size` members of this gender finished with a place
+    pub complete: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RaceScore {
+    pub race: String,
+    pub date: String,
+    pub finishers: Vec<ScoredFinisher>,
+    pub teams: Vec<TeamScore>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MemberStanding {
+    pub rank: usize,
+    pub member_number: usize,
+    pub member: String,
+    pub gender: Option<Gender>,
+    pub race_count: usize,
+    pub points: u32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Standings {
+    pub races: Vec<RaceScore>,
+    pub members: Vec<MemberStanding>,
+}
+
+// `season_report` must come from `season::find_season_matches` over the same `race_list`. Its
+// participation probability decides which matches count as confirmed.
+//
+// A line whose gender isn't the one the members file gives the member doesn't count for them.
+// As with `Assignment::OneToOne`, a line counts for at most one member, the most likely.
+pub fn score(scoring: &Scoring, season_report: &SeasonReport, race_list: &[Race]) -> Standings {
+    let mut race_score_list = Vec::new();
+    for (race_index, race) in race_list.iter().enumerate() {
+        let finisher_list = season_report
+            .members
+            .iter()
+            .flat_map(|season| {
+                season
+                    .races
+                    .iter()
+                    .filter(|member_race| member_race.race_index == race_index)
+                    .map(move |member_race| (season, member_race))
+            })
+            .filter(
+                |(season, member_race)| match (season.gender, member_race.fields.gender) {
+                    (Some(member_gender), Some(line_gender)) => member_gender == line_gender,
+                    _ => true,
+                },
+            )
+            .sorted_by(|(a_season, a_race), (b_season, b_race)| {
+                b_race
+                    .prob
+                    .partial_cmp(&a_race.prob)
+                    .unwrap()
+                    .then(a_season.member_number.cmp(&b_season.member_number))
+            })
+            .unique_by(|(_, member_race)| member_race.line_number)
+            .map(|(season, member_race)| ScoredFinisher {
+                member_number: season.member_number,
+                member: season.member.clone(),
+                gender: member_race.fields.gender,
+                place: member_race.fields.place,
+                time: member_race.fields.time,
+                club_place: 0,
+                points: 0,
+            })
+            .collect_vec();
+        race_score_list.push(score_race(scoring, race, finisher_list));
+    }
+
+    Standings {
+        members: standings(&race_score_list),
+        races: race_score_list,
+    }
+}
+
+fn score_race(scoring: &Scoring, race: &Race, mut finisher_list: Vec<ScoredFinisher>) -> RaceScore {
+    // Finishers without a place or time go last
+    finisher_list.sort_by(|a, b| {
+        a.gender
+            .cmp(&b.gender)
+            .then(a.place.is_none().cmp(&b.place.is_none()))
+            .then(a.place.cmp(&b.place))
+            .then(a.time.is_none().cmp(&b.time.is_none()))
+            .then(a.time.partial_cmp(&b.time).unwrap())
+            .then(a.member_number.cmp(&b.member_number))
+    });
+
+    let mut team_list = Vec::new();
+    for (gender, group) in &finisher_list
+        .iter_mut()
+        .group_by(|finisher| finisher.gender)
+    {
+        let mut team = TeamScore {
+            gender,
+            score: 0,
+            member_numbers: Vec::new(),
+            complete: false,
+        };
+        for (index, finisher) in group.enumerate() {
+            finisher.club_place = index + 1;
+            finisher.points = scoring
+                .points_table
+                .0
+                .get(index)
+                .copied()
+                .unwrap_or(scoring.participation_points);
+            if let Some(place) = finisher.place {
+                if team.member_numbers.len() < scoring.team_size {
+                    team.score += place;
+                    team.member_numbers.push(finisher.member_number);
+                }
+            }
+        }
+        team.complete = team.member_numbers.len() == scoring.team_size;
+        team_list.push(team);
+    }
+
+    RaceScore {
+        race: race.name.clone(),
+        date: race.date.clone(),
+        finishers: finisher_list,
+        teams: team_list,
+    }
+}
+
+// Most points first; ties share a rank
+fn standings(race_score_list: &[RaceScore]) -> Vec<MemberStanding> {
+    let mut number_to_standing = HashMap::<usize, MemberStanding>::new();
+    for finisher in race_score_list
+        .iter()
+        .flat_map(|race| race.finishers.iter())
+    {
+        let standing = number_to_standing
+            .entry(finisher.member_number)
+            .or_insert_with(|| MemberStanding {
+                rank: 0,
+                member_number: finisher.member_number,
+                member: finisher.member.clone(),
+                gender: None,
+                race_count: 0,
+                points: 0,
+            });
+        standing.gender = standing.gender.or(finisher.gender);
+        standing.race_count += 1;
+        standing.points += finisher.points;
+    }
+    let mut standing_list = number_to_standing.into_values().collect_vec();
+    standing_list.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then(a.member_number.cmp(&b.member_number))
+    });
+    for index in 0..standing_list.len() {
+        standing_list[index].rank =
+            if index > 0 && standing_list[index].points == standing_list[index - 1].points {
+                standing_list[index - 1].rank
+            } else {
+                index + 1
+            };
+    }
+    standing_list
+}
+
+fn gender_text(gender: Option<Gender>) -> String {
+    gender.map(|gender| gender.to_string()).unwrap_or_default()
+}
+
+impl Standings {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap() // only plain data, so can't fail
+    }
+
+    // The season leaderboard
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("rank,member_number,member,gender,races,points\n");
+        for standing in self.members.iter() {
+            writeln!(
+                csv,
+                "{},{},\"{}\",{},{},{}",
+                standing.rank,
+                standing.member_number,
+                standing.member.replace('"', "\"\""),
+                gender_text(standing.gender),
+                standing.race_count,
+                standing.points
+            )
+            .unwrap(); // writing to a String can't fail
+        }
+        csv
+    }
+
+    // Each race's club finishers and team scores, one row per finisher
+    pub fn races_to_csv(&self) -> String {
+        let mut csv =
+            String::from("race,date,gender,club_place,member_number,member,place,time,points\n");
+        for race in self.races.iter() {
+            for finisher in race.finishers.iter() {
+                writeln!(
+                    csv,
+                    "\"{}\",{},{},{},{},\"{}\",{},{},{}",
+                    race.race.replace('"', "\"\""),
+                    race.date,
+                    gender_text(finisher.gender),
+                    finisher.club_place,
+                    finisher.member_number,
+                    finisher.member.replace('"', "\"\""),
+                    finisher.place.map(|p| p.to_string()).unwrap_or_default(),
+                    finisher.time.map(format_time).unwrap_or_default(),
+                    finisher.points
+                )
+                .unwrap();
+            }
+        }
+        csv
+    }
+
+    // A self-contained page: the leaderboard, then each race with its team scores.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        writeln!(
+            html,
+            "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Club standings</title>
+<style>
+table {{ border-collapse: collapse; }}
+td, th {{ border: 1px solid #ccc; padding: 2px 8px; }}
+.numeric {{ text-align: right; }}
+</style>
+</head>
+<body>
+<h1>Club standings</h1>
+<table>
+<tr><th>rank</th><th>member</th><th>gender</th><th>races</th><th>points</th></tr>"
+        )
+        .unwrap();
+        for standing in self.members.iter() {
+            writeln!(
+                html,
+                "<tr><td class=\"numeric\">{}</td><td>{}</td><td>{}</td><td class=\"numeric\">{}</td><td class=\"numeric\">{}</td></tr>",
+                standing.rank,
+                escape_html(&standing.member),
+                gender_text(standing.gender),
+                standing.race_count,
+                standing.points
+            )
+            .unwrap();
+        }
+        writeln!(html, "</table>").unwrap();
+
+        for race in self.races.iter() {
+            writeln!(
+                html,
+                "<h2>{} ({})</h2>",
+                escape_html(&race.race),
+                escape_html(&race.date)
+            )
+            .unwrap();
+            for team in race.teams.iter() {
+                writeln!(
+                    html,
+                    "<p>Team {}: {}{}</p>",
+                    gender_text(team.gender),
+                    team.score,
+                    if team.complete { "" } else { " (incomplete)" }
+                )
+                .unwrap();
+            }
+            writeln!(
+                html,
+                "<table>\n<tr><th>gender</th><th>club place</th><th>member</th><th>place</th><th>time</th><th>points</th></tr>"
+            )
+            .unwrap();
+            for finisher in race.finishers.iter() {
+                writeln!(
+                    html,
+                    "<tr><td>{}</td><td class=\"numeric\">{}</td><td>{}</td><td class=\"numeric\">{}</td><td class=\"numeric\">{}</td><td class=\"numeric\">{}</td></tr>",
+                    gender_text(finisher.gender),
+                    finisher.club_place,
+                    escape_html(&finisher.member),
+                    finisher.place.map(|p| p.to_string()).unwrap_or_default(),
+                    finisher.time.map(format_time).unwrap_or_default(),
+                    finisher.points
+                )
+                .unwrap();
+            }
+            writeln!(html, "</table>").unwrap();
+        }
+        writeln!(html, "</body>\n</html>").unwrap();
+        html
+    }
+}
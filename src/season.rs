@@ -0,0 +1,347 @@
+// Match one roster against a whole season of races. The members are parsed and indexed once
+// (see `MemberIndex`) and reused for every race. The summary lists, for each member, the
+// races they probably ran.
+//
+// A manifest file lists the races, one "name<tab>date<tab>results file" per line. Results
+// files are relative to the manifest. Blank lines and lines starting with '#' are ignored.
+// Instead of a manifest, a zip archive of results files makes each file a race.
+
+use core::fmt::Write;
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::fields::{Gender, ResultFields};
+use crate::report::{self, escape_html, LineReport};
+use crate::zip::ZipArchive;
+use crate::{
+    input_from_bytes, read_all_lines, read_input, Config, Encoding, IncludeCity, SheetOptions,
+};
+
+pub struct Race {
+    pub name: String,
+    pub date: String,
+    pub result_lines: Vec<String>,
+    // How the results file was decoded, when it's a text file
+    pub encoding: Option<Encoding>,
+}
+
+impl Race {
+    // The first four-digit number in the date, such as 2023 in "2023-06-01" or "6/1/2023"
+    pub fn year(&self) -> Option<u32> {
+        self.date
+            .split(|c: char| !c.is_ascii_digit())
+            .find(|part| part.len() == 4)
+            .and_then(|part| part.parse().ok())
+    }
+}
+
+// `sheet_options` applies to results spreadsheets
+pub fn load_manifest(
+    path: &Path,
+    sheet_options: &SheetOptions,
+) -> Result<Vec<Race>, anyhow::Error> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut race_list = Vec::new();
+    for line in read_all_lines(path)? {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, date, results)) = line.split('\t').collect_tuple() else {
+            anyhow::bail!("Manifest line should be 'name<tab>date<tab>results file', not '{line}'");
+        };
+        let (result_lines, encoding) = read_input(dir.join(results.trim()), sheet_options)
+            .with_context(|| format!("Race '{name}'"))?;
+        race_list.push(Race {
+            name: name.trim().to_string(),
+            date: date.trim().to_string(),
+            result_lines,
+            encoding,
+        });
+    }
+    Ok(race_list)
+}
+
+// Every results file in the archive, in the order stored, is a race named by its entry name,
+// such as "2019/turkey_trot.csv". The races have no dates. Directories, hidden files, and macOS
+// metadata ("__MACOSX/") are skipped.
+pub fn load_archive(path: &Path, sheet_options: &SheetOptions) -> Result<Vec<Race>, anyhow::Error> {
+    let reading_error = || format!("Reading '{}'", path.display());
+    let bytes = std::fs::read(path).with_context(reading_error)?;
+    let archive = ZipArchive::new(&bytes).with_context(reading_error)?;
+    let mut race_list = Vec::new();
+    for name in archive.names() {
+        if name.ends_with('/')
+            || name
+                .split('/')
+                .any(|part| part.starts_with('.') || part == "__MACOSX")
+        {
+            continue;
+        }
+        let (result_lines, encoding) = archive
+            .read(name)
+            .and_then(|bytes| input_from_bytes(name, &bytes, sheet_options))
+            .with_context(|| format!("Race '{name}' in '{}'", path.display()))?;
+        race_list.push(Race {
+            name: name.to_string(),
+            date: String::new(),
+            result_lines,
+            encoding,
+        });
+    }
+    if race_list.is_empty() {
+        anyhow::bail!("'{}' has no results files", path.display());
+    }
+    Ok(race_list)
+}
+
+// A ".zip" archive (see `load_archive`) or else a manifest (see `load_manifest`)
+pub fn load_races(path: &Path, sheet_options: &SheetOptions) -> Result<Vec<Race>, anyhow::Error> {
+    let is_zip = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"));
+    if is_zip {
+        load_archive(path, sheet_options)
+    } else {
+        load_manifest(path, sheet_options)
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RaceReport {
+    pub name: String,
+    pub date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_prob_member_in_race: Option<f32>,
+    pub lines: Vec<LineReport>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MemberRace {
+    // 0-based, in manifest order
+    #[serde(skip)]
+    pub race_index: usize,
+    pub race: String,
+    pub date: String,
+    pub line_number: usize,
+    pub line: String,
+    pub fields: ResultFields,
+    pub prob: f32,
+}
+
+// Members who ran at least one race, most races first
+#[derive(Serialize, Clone, Debug)]
+pub struct MemberSeason {
+    pub member_number: usize,
+    pub member: String,
+    // From the members file
+    pub gender: Option<Gender>,
+    pub race_count: usize,
+    pub races: Vec<MemberRace>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SeasonReport {
+    pub races: Vec<RaceReport>,
+    pub members: Vec<MemberSeason>,
+}
+
+// A member counts as having run a race if their most likely line there has at least
+// `participation_probability`.
+pub fn find_season_matches(
+    config: &Config,
+    member_lines: &[String],
+    race_list: &[Race],
+    include_city: IncludeCity,
+    participation_probability: f32,
+) -> Result<SeasonReport, anyhow::Error> {
+    let member_index = config.index_members(member_lines)?;
+
+    let mut race_report_list = Vec::new();
+    let mut id_to_season = HashMap::<usize, MemberSeason>::new();
+    for (race_index, race) in race_list.iter().enumerate() {
+        let (prob_member_in_race, line_people_list) = config
+            .find_line_people_in(
+                &member_index,
+                &race.result_lines,
+                include_city,
+                race.year().or(config.race_year),
+            )
+            .with_context(|| format!("Race '{}'", race.name))?;
+
+        // Each member's most likely line in this race
+        let mut id_to_best = HashMap::<usize, MemberRace>::new();
+        for line_people in line_people_list.iter() {
+            for person_prob in line_people.person_prob_list.iter() {
+                if person_prob.prob < participation_probability {
+                    continue;
+                }
+                let id = person_prob.person.id;
+                if id_to_best
+                    .get(&id)
+                    .is_some_and(|best| best.prob >= person_prob.prob)
+                {
+                    continue;
+                }
+                id_to_best.insert(
+                    id,
+                    MemberRace {
+                        race_index,
+                        race: race.name.clone(),
+                        date: race.date.clone(),
+                        line_number: line_people.line_index + 1,
+                        line: line_people.line.clone(),
+                        fields: line_people.fields.clone(),
+                        prob: person_prob.prob,
+                    },
+                );
+                id_to_season.entry(id).or_insert_with(|| MemberSeason {
+                    member_number: id + 1,
+                    member: person_prob.person.input_pretty.clone(),
+                    gender: person_prob.person.gender,
+                    race_count: 0,
+                    races: Vec::new(),
+                });
+            }
+        }
+        for (id, member_race) in id_to_best {
+            let season = id_to_season.get_mut(&id).unwrap(); // inserted above
+            season.race_count += 1;
+            season.races.push(member_race);
+        }
+
+        race_report_list.push(RaceReport {
+            name: race.name.clone(),
+            date: race.date.clone(),
+            estimated_prob_member_in_race: config
+                .estimate_prob_member_in_race
+                .then_some(prob_member_in_race),
+            lines: report::line_reports(&line_people_list),
+        });
+    }
+
+    // Races are visited in manifest order, so each member's races already are, too
+    let mut member_season_list = id_to_season.into_values().collect_vec();
+    member_season_list.sort_by(|a, b| {
+        b.race_count
+            .cmp(&a.race_count)
+            .then(a.member_number.cmp(&b.member_number))
+    });
+
+    Ok(SeasonReport {
+        races: race_report_list,
+        members: member_season_list,
+    })
+}
+
+impl SeasonReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap() // only plain data, so can't fail
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "Season summary").unwrap();
+        for season in self.members.iter() {
+            writeln!(text, "{}\t{} races", season.member, season.race_count).unwrap();
+            for member_race in season.races.iter() {
+                writeln!(
+                    text,
+                    "\t{}\t{}\t{:>3.0}%\t{}: {}",
+                    member_race.date,
+                    member_race.race,
+                    member_race.prob * 100.0,
+                    member_race.line_number,
+                    member_race.line
+                )
+                .unwrap();
+            }
+        }
+        for race_report in self.races.iter() {
+            writeln!(text, "\n{} ({})", race_report.name, race_report.date).unwrap();
+            for line in report::lines_to_text(&race_report.lines) {
+                writeln!(text, "{line}").unwrap();
+            }
+        }
+        text
+    }
+
+    // A self-contained page: the season summary, then each race.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        writeln!(
+            html,
+            "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Season summary</title>
+<style>
+table {{ border-collapse: collapse; }}
+td, th {{ border: 1px solid #ccc; padding: 2px 8px; }}
+.numeric {{ text-align: right; }}
+</style>
+</head>
+<body>
+<h1>Season summary</h1>
+<table>
+<tr><th>member</th><th>races</th><th>which</th></tr>"
+        )
+        .unwrap();
+        for season in self.members.iter() {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"numeric\">{}</td><td>{}</td></tr>",
+                escape_html(&season.member),
+                season.race_count,
+                season
+                    .races
+                    .iter()
+                    .map(|member_race| format!(
+                        "{} {} ({:.0}%)",
+                        escape_html(&member_race.date),
+                        escape_html(&member_race.race),
+                        member_race.prob * 100.0
+                    ))
+                    .join("<br>")
+            )
+            .unwrap();
+        }
+        writeln!(html, "</table>").unwrap();
+        for race_report in self.races.iter() {
+            writeln!(
+                html,
+                "<h2>{} ({})</h2>",
+                escape_html(&race_report.name),
+                escape_html(&race_report.date)
+            )
+            .unwrap();
+            writeln!(html, "<table>").unwrap();
+            for line_report in race_report.lines.iter() {
+                writeln!(
+                    html,
+                    "<tr><td>{}: {}</td><td>{}</td></tr>",
+                    line_report.line_number,
+                    escape_html(&line_report.line),
+                    line_report
+                        .members
+                        .iter()
+                        .map(|candidate| format!(
+                            "{:.0}% {}",
+                            candidate.prob * 100.0,
+                            escape_html(&candidate.member)
+                        ))
+                        .join("<br>")
+                )
+                .unwrap();
+            }
+            writeln!(html, "</table>").unwrap();
+        }
+        writeln!(html, "</body>\n</html>").unwrap();
+        html
+    }
+}
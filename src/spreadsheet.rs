@@ -0,0 +1,424 @@
+// Members and results from spreadsheets: Excel (.xlsx) and OpenDocument (.ods). Both are zip
+// files of XML (see `zip` and `markup`). One sheet becomes tab-separated lines, one per
+// non-empty row, which the matcher reads like any other members or results file.
+//
+// Excel stores finish times as fractions of a day, shown with a time format, so cells with a
+// time format are written out as times ("35:07"). OpenDocument files store the text as shown.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use itertools::Itertools;
+
+use crate::fields::format_time;
+use crate::markup::{self, Event};
+use crate::zip::{self, ZipArchive};
+
+// Rows wider than this are cut off, in case a sheet formats a whole row
+const MAX_COLUMNS: usize = 1024;
+// Excel's last row, so that a row number can't ask for billions of empty rows
+const MAX_ROWS: usize = 1_048_576;
+// An empty row repeated to the bottom of an .ods sheet is skipped, but a non-empty one is
+// repeated at most this many times
+const MAX_REPEATED_ROWS: usize = 1000;
+
+// Which sheet to read (by name or 1-based number; by default the first) and which row, if any,
+// is the header row (1-based). Rows above the header row are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct SheetOptions {
+    pub sheet: Option<String>,
+    pub header_row: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SpreadsheetKind {
+    Xlsx,
+    Ods,
+}
+
+pub fn is_spreadsheet(bytes: &[u8]) -> bool {
+    zip::is_zip(bytes)
+        && ZipArchive::new(bytes).is_ok_and(|archive| {
+            archive
+                .names()
+                .any(|name| name == "xl/workbook.xml" || name == "content.xml")
+        })
+}
+
+// The kind is read from the file itself
+pub fn to_lines(bytes: &[u8], options: &SheetOptions) -> Result<Vec<String>, anyhow::Error> {
+    let archive = ZipArchive::new(bytes)?;
+    let kind = if archive.names().any(|name| name == "xl/workbook.xml") {
+        SpreadsheetKind::Xlsx
+    } else if archive.names().any(|name| name == "content.xml") {
+        SpreadsheetKind::Ods
+    } else {
+        anyhow::bail!("Zip file is neither an .xlsx nor an .ods spreadsheet");
+    };
+    let row_list = match kind {
+        SpreadsheetKind::Xlsx => xlsx_rows(&archive, options.sheet.as_deref())?,
+        SpreadsheetKind::Ods => ods_rows(&archive, options.sheet.as_deref())?,
+    };
+    Ok(row_list
+        .into_iter()
+        .skip(
+            options
+                .header_row
+                .map_or(0, |header_row| header_row.saturating_sub(1)),
+        )
+        .filter_map(|row| {
+            let field_list = row
+                .iter()
+                .map(|field| field.split_whitespace().join(" "))
+                .collect_vec();
+            let len = field_list
+                .iter()
+                .rposition(|field| !field.is_empty())
+                .map_or(0, |last| last + 1);
+            (len > 0).then(|| field_list[..len].join("\t"))
+        })
+        .collect())
+}
+
+// Picks a sheet by name (ignoring case) or 1-based number
+fn choose_sheet<'a, T>(
+    sheet_list: &'a [(String, T)],
+    sheet: Option<&str>,
+) -> Result<&'a T, anyhow::Error> {
+    let Some(sheet) = sheet else {
+        return sheet_list
+            .first()
+            .map(|(_, value)| value)
+            .context("Spreadsheet has no sheets");
+    };
+    sheet_list
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(sheet))
+        .or_else(|| {
+            sheet
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| sheet_list.get(number.checked_sub(1)?))
+        })
+        .map(|(_, value)| value)
+        .with_context(|| {
+            format!(
+                "Spreadsheet has no sheet '{sheet}' (sheets are {})",
+                sheet_list
+                    .iter()
+                    .map(|(name, _)| format!("'{name}'"))
+                    .join(", ")
+            )
+        })
+}
+
+// "B7" is column 1, and a reference without letters has none
+fn column_index(cell_ref: &str) -> Result<Option<usize>, anyhow::Error> {
+    let letters = cell_ref
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>();
+    if letters.is_empty() {
+        return Ok(None);
+    }
+    letters
+        .chars()
+        .try_fold(0usize, |index, c| {
+            index
+                .checked_mul(26)?
+                .checked_add(c.to_ascii_uppercase() as usize - 'A' as usize + 1)
+        })
+        .map(|index| Some(index - 1))
+        .with_context(|| format!("Cell reference '{cell_ref}' is out of range"))
+}
+
+fn xlsx_rows(archive: &ZipArchive, sheet: Option<&str>) -> Result<Vec<Vec<String>>, anyhow::Error> {
+    let workbook = archive.read_string("xl/workbook.xml")?;
+    let id_to_target = markup::events(&archive.read_string("xl/_rels/workbook.xml.rels")?)
+        .filter_map(|event| match event {
+            Event::Start {
+                name, attributes, ..
+            } if name == "relationship" => Some((
+                attributes.get("id")?.clone(),
+                attributes.get("target")?.clone(),
+            )),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let sheet_list = markup::events(&workbook)
+        .filter_map(|event| match event {
+            Event::Start {
+                name, attributes, ..
+            } if name == "sheet" => {
+                let target = id_to_target.get(attributes.get("r:id")?)?;
+                let path = match target.strip_prefix('/') {
+                    Some(absolute) => absolute.to_string(),
+                    None => format!("xl/{target}"),
+                };
+                Some((attributes.get("name")?.clone(), path))
+            }
+            _ => None,
+        })
+        .collect_vec();
+    let path = choose_sheet(&sheet_list, sheet)?;
+
+    let shared_string_list = match archive.read_string("xl/sharedStrings.xml") {
+        Ok(text) => shared_strings(&text),
+        Err(_) => Vec::new(),
+    };
+    let time_style_list = match archive.read_string("xl/styles.xml") {
+        Ok(text) => time_styles(&text),
+        Err(_) => Vec::new(),
+    };
+
+    let mut row_list = Vec::new();
+    let mut row = Vec::new();
+    // The current cell's column, type, and style, and its text so far
+    let mut cell = None;
+    let mut text = String::new();
+    let mut in_value = false;
+    for event in markup::events(&archive.read_string(path)?) {
+        match event {
+            Event::Start {
+                name,
+                attributes,
+                self_closing,
+            } if name == "row" => {
+                // Empty rows may be left out, so use the row number to keep header rows in place
+                if let Some(number) = attributes.get("r").and_then(|r| r.parse::<usize>().ok()) {
+                    if number > MAX_ROWS {
+                        anyhow::bail!("Row {number} is past the last row ({MAX_ROWS})");
+                    }
+                    while row_list.len() + 1 < number {
+                        row_list.push(Vec::new());
+                    }
+                }
+                row = Vec::new();
+                if self_closing {
+                    row_list.push(Vec::new());
+                }
+            }
+            Event::End { name } if name == "row" => row_list.push(core::mem::take(&mut row)),
+            Event::Start {
+                name,
+                attributes,
+                self_closing,
+            } if name == "c" => {
+                let column = match attributes.get("r") {
+                    Some(cell_ref) => column_index(cell_ref)?,
+                    None => None,
+                }
+                .unwrap_or(row.len());
+                let cell_type = attributes.get("t").cloned().unwrap_or_default();
+                let style = attributes.get("s").and_then(|s| s.parse::<usize>().ok());
+                cell = (!self_closing).then_some((column, cell_type, style));
+                text.clear();
+            }
+            Event::Start { name, .. } if name == "v" || name == "t" => in_value = true,
+            Event::End { name } if name == "v" || name == "t" => in_value = false,
+            Event::Text(value) if in_value => text.push_str(&value),
+            Event::End { name } if name == "c" => {
+                let Some((column, cell_type, style)) = cell.take() else {
+                    continue;
+                };
+                if column >= MAX_COLUMNS {
+                    continue;
+                }
+                let value = match cell_type.as_str() {
+                    "s" => text
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|index| shared_string_list.get(index))
+                        .cloned()
+                        .unwrap_or_default(),
+                    "b" => (if text == "1" { "TRUE" } else { "FALSE" }).to_string(),
+                    "" | "n" => {
+                        let is_time = style
+                            .and_then(|style| time_style_list.get(style))
+                            .copied()
+                            .unwrap_or(false);
+                        match text.trim().parse::<f64>() {
+                            Ok(days) if is_time => format_time((days * 86400.0) as f32),
+                            _ => text.clone(),
+                        }
+                    }
+                    _ => text.clone(),
+                };
+                if row.len() <= column {
+                    row.resize(column + 1, String::new());
+                }
+                row[column] = value;
+            }
+            _ => {}
+        }
+    }
+    Ok(row_list)
+}
+
+// Each <si> is one string, possibly in several runs. Phonetic guides (<rPh>) are left out.
+fn shared_strings(text: &str) -> Vec<String> {
+    let mut string_list = Vec::new();
+    let mut current = String::new();
+    let mut in_text = false;
+    let mut in_phonetic = false;
+    for event in markup::events(text) {
+        match event {
+            Event::Start { name, .. } if name == "si" => current.clear(),
+            Event::End { name } if name == "si" => string_list.push(core::mem::take(&mut current)),
+            Event::Start { name, .. } if name == "rph" => in_phonetic = true,
+            Event::End { name } if name == "rph" => in_phonetic = false,
+            Event::Start { name, .. } if name == "t" => in_text = !in_phonetic,
+            Event::End { name } if name == "t" => in_text = false,
+            Event::Text(value) if in_text => current.push_str(&value),
+            _ => {}
+        }
+    }
+    string_list
+}
+
+// For each cell style (<cellXfs>), whether its number format shows a time of day or a duration
+fn time_styles(text: &str) -> Vec<bool> {
+    let mut custom_time_format_set = std::collections::HashSet::new();
+    let mut in_cell_formats = false;
+    let mut time_style_list = Vec::new();
+    for event in markup::events(text) {
+        match event {
+            Event::Start {
+                name, attributes, ..
+            } if name == "numfmt" => {
+                if let (Some(id), Some(code)) =
+                    (attributes.get("numfmtid"), attributes.get("formatcode"))
+                {
+                    if is_time_format(code) {
+                        custom_time_format_set.insert(id.clone());
+                    }
+                }
+            }
+            Event::Start { name, .. } if name == "cellxfs" => in_cell_formats = true,
+            Event::End { name } if name == "cellxfs" => in_cell_formats = false,
+            Event::Start {
+                name, attributes, ..
+            } if name == "xf" && in_cell_formats => {
+                let id = attributes.get("numfmtid").cloned().unwrap_or_default();
+                // Excel's built-in time formats
+                let is_builtin_time =
+                    matches!(id.as_str(), "18" | "19" | "20" | "21" | "45" | "46" | "47");
+                time_style_list.push(is_builtin_time || custom_time_format_set.contains(&id));
+            }
+            _ => {}
+        }
+    }
+    time_style_list
+}
+
+// Hours or seconds, but no year or day, such as "h:mm:ss" or "[mm]:ss.0"
+fn is_time_format(code: &str) -> bool {
+    // Quoted text isn't part of the format
+    let code = code
+        .split('"')
+        .step_by(2)
+        .collect::<String>()
+        .to_lowercase();
+    !code.contains('y') && !code.contains('d') && (code.contains('h') || code.contains('s'))
+}
+
+fn ods_rows(archive: &ZipArchive, sheet: Option<&str>) -> Result<Vec<Vec<String>>, anyhow::Error> {
+    let content = archive.read_string("content.xml")?;
+    // Each sheet's rows, in order
+    let mut sheet_list: Vec<(String, Vec<Vec<String>>)> = Vec::new();
+    let mut row = Vec::new();
+    let mut row_repeat = 1;
+    let mut cell_repeat = 1;
+    let mut text = String::new();
+    let mut paragraph_count = 0;
+    let mut in_annotation = false;
+    for event in markup::events(&content) {
+        match event {
+            Event::Start {
+                name, attributes, ..
+            } if name == "table:table" => {
+                let sheet_name = attributes.get("table:name").cloned().unwrap_or_default();
+                sheet_list.push((sheet_name, Vec::new()));
+            }
+            Event::Start {
+                name,
+                attributes,
+                self_closing,
+            } if name == "table:table-row" => {
+                row = Vec::new();
+                row_repeat = repeat(&attributes, "table:number-rows-repeated");
+                if self_closing {
+                    push_rows(&mut sheet_list, Vec::new(), row_repeat);
+                }
+            }
+            Event::End { name } if name == "table:table-row" => {
+                push_rows(&mut sheet_list, core::mem::take(&mut row), row_repeat);
+            }
+            Event::Start {
+                name,
+                attributes,
+                self_closing,
+            } if name == "table:table-cell" || name == "table:covered-table-cell" => {
+                cell_repeat = repeat(&attributes, "table:number-columns-repeated");
+                text.clear();
+                paragraph_count = 0;
+                if self_closing {
+                    push_cells(&mut row, "", cell_repeat);
+                }
+            }
+            Event::End { name }
+                if name == "table:table-cell" || name == "table:covered-table-cell" =>
+            {
+                push_cells(&mut row, &text, cell_repeat);
+            }
+            // A comment on a cell isn't part of its text
+            Event::Start {
+                name, self_closing, ..
+            } if name == "office:annotation" => in_annotation = !self_closing,
+            Event::End { name } if name == "office:annotation" => in_annotation = false,
+            _ if in_annotation => {}
+            Event::Start { name, .. } if name == "text:p" => {
+                if paragraph_count > 0 {
+                    text.push(' ');
+                }
+                paragraph_count += 1;
+            }
+            Event::Start {
+                name, attributes, ..
+            } if name == "text:s" => {
+                text.push_str(&" ".repeat(repeat(&attributes, "text:c")));
+            }
+            Event::Start { name, .. } if name == "text:tab" => text.push(' '),
+            Event::Text(value) if paragraph_count > 0 => text.push_str(&value),
+            _ => {}
+        }
+    }
+    Ok(choose_sheet(&sheet_list, sheet)?.clone())
+}
+
+fn repeat(attributes: &HashMap<String, String>, name: &str) -> usize {
+    attributes
+        .get(name)
+        .and_then(|count| count.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1)
+}
+
+fn push_cells(row: &mut Vec<String>, text: &str, cell_repeat: usize) {
+    let count = cell_repeat.min(MAX_COLUMNS.saturating_sub(row.len()));
+    row.extend(core::iter::repeat_n(text.to_string(), count));
+}
+
+fn push_rows(sheet_list: &mut [(String, Vec<Vec<String>>)], row: Vec<String>, row_repeat: usize) {
+    let Some((_, row_list)) = sheet_list.last_mut() else {
+        return;
+    };
+    let is_empty = row.iter().all(|field| field.trim().is_empty());
+    let count = if is_empty {
+        1
+    } else {
+        row_repeat.min(MAX_REPEATED_ROWS)
+    };
+    row_list.extend(core::iter::repeat_n(row, count));
+}
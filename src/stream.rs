@@ -0,0 +1,293 @@
+// Matching for races too big to hold in memory, such as 50,000 finishers or years of archived
+// results. The results are read twice, from any `BufRead`, a chunk of lines at a time. The first
+// pass counts, for each token, how many results have it, and counts bibs, ages, and genders,
+// which is all that stop words and coincidences need. The second pass scores each chunk against
+// the whole race's counts and passes each matched line on as soon as its chunk is done, in file
+// order.
+//
+// A chunk ends where a line starts a new result (with a place) or is blank, so that results
+// split across lines are joined within one chunk. The last header row and section heading are
+// carried into the next chunk, so the columns and gender they give still apply. Streaming can't
+// estimate prob_member_in_race, assign one-to-one, or choose whether to include cities ("auto"),
+// since each of those needs every line's scores first.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::encoding::{self, DecodedLines, Encoding};
+use crate::evidence::{FieldCounts, RaceFields};
+use crate::fields::heading_gender;
+use crate::records::leading_place;
+use crate::report::{self, LineReport};
+use crate::{log_odds, Assignment, Config, IncludeCity, MemberIndex, ParsedResults, Token};
+
+// What the first pass learns about the results
+pub struct TokenCounts {
+    // How many results (after joining lines) the race has
+    pub results_count: usize,
+    token_to_line_count: HashMap<Token, usize>,
+    field_counts: FieldCounts,
+    // How the results were decoded (see `encoding::decode_lines`)
+    pub encoding: Option<Encoding>,
+}
+
+// A text results file, unzipped if it ends in ".gz". Other formats (see `read_input`) are read
+// whole, so they can't be streamed.
+pub fn open_results(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    if let Some("csv" | "html" | "htm" | "pdf" | "xlsx" | "ods" | "zip") = extension.as_deref() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Only text results can be streamed, not '{}'",
+                path.display()
+            ),
+        ));
+    }
+    let file = File::open(path)?;
+    let is_gzip = extension.as_deref() == Some("gz");
+    Ok(if is_gzip {
+        Box::new(BufReader::new(libflate::gzip::MultiDecoder::new(file)?))
+    } else {
+        Box::new(BufReader::new(file))
+    })
+}
+
+// A chunk's lines, parsed after the lines carried from earlier chunks
+struct Chunk {
+    parsed: ParsedResults,
+    carried: usize,
+    // The line number (0-based) in the file of the chunk's own first line
+    start: usize,
+}
+
+impl Chunk {
+    // The indexes of the chunk's own results, leaving out those carried
+    fn own_indexes(&self) -> impl Iterator<Item = usize> + '_ {
+        let start_list = &self.parsed.records.start_list;
+        (0..start_list.len()).filter(|index| start_list[*index] >= self.carried)
+    }
+}
+
+struct Chunks<'a, R: BufRead> {
+    config: &'a Config,
+    line_iter: DecodedLines<R>,
+    carry_list: Vec<String>,
+    // The first line of the next chunk, read while looking for the end of this one
+    pending: Option<String>,
+    next_start: usize,
+}
+
+impl<'a, R: BufRead> Chunks<'a, R> {
+    fn new(config: &'a Config, reader: R) -> Self {
+        Self {
+            config,
+            line_iter: encoding::decode_lines(reader),
+            carry_list: Vec::new(),
+            pending: None,
+            next_start: 0,
+        }
+    }
+
+    // With a fixed number of lines per result, chunks are whole results and nothing is carried
+    fn lines_per_record(&self) -> Option<usize> {
+        self.config.lines_per_record.filter(|n| *n > 1)
+    }
+
+    fn ends_before(&self, line_list: &[String], line: &str) -> bool {
+        let chunk_lines = self.config.stream_chunk_lines;
+        match self.lines_per_record() {
+            Some(n) => line_list.len() >= chunk_lines && line_list.len().is_multiple_of(n),
+            None => {
+                line_list.len() >= 2 * chunk_lines
+                    || (line_list.len() >= chunk_lines
+                        && (line.trim().is_empty() || leading_place(line).is_some()))
+            }
+        }
+    }
+
+    fn next_chunk(&mut self) -> io::Result<Option<Chunk>> {
+        let mut line_list = self.pending.take().into_iter().collect::<Vec<_>>();
+        while let Some(line) = self.line_iter.next().transpose()? {
+            if self.ends_before(&line_list, &line) {
+                self.pending = Some(line);
+                break;
+            }
+            line_list.push(line);
+        }
+        if line_list.is_empty() {
+            return Ok(None);
+        }
+        let own_count = line_list.len();
+        let carried = self.carry_list.len();
+        line_list.splice(0..0, self.carry_list.drain(..));
+        let parsed = self.config.parse_results(&line_list);
+
+        if self.lines_per_record().is_none() {
+            let record_lines = &parsed.records.line_list;
+            let last_header = (0..record_lines.len())
+                .rev()
+                .find(|index| parsed.layout.is_header(*index));
+            let last_heading = (0..record_lines.len())
+                .rev()
+                .find(|index| heading_gender(&record_lines[*index]).is_some());
+            let mut index_list = [last_header, last_heading]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            index_list.sort();
+            index_list.dedup();
+            self.carry_list = index_list
+                .into_iter()
+                .map(|index| record_lines[index].clone())
+                .collect();
+        }
+
+        let start = self.next_start;
+        self.next_start += own_count;
+        Ok(Some(Chunk {
+            parsed,
+            carried,
+            start,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for Chunks<'_, R> {
+    type Item = io::Result<Chunk>;
+
+    fn next(&mut self) -> Option<io::Result<Chunk>> {
+        self.next_chunk().transpose()
+    }
+}
+
+impl Config {
+    // The first pass. Only words that members have are counted (see `intern`), which is why it
+    // takes the members' index, though it only needs them to have been indexed.
+    pub fn count_tokens<R: BufRead>(
+        &self,
+        _member_index: &MemberIndex,
+        reader: R,
+    ) -> io::Result<TokenCounts> {
+        let mut chunks = Chunks::new(self, reader);
+        let mut results_count = 0;
+        let mut token_to_line_count = HashMap::new();
+        let mut field_counts = FieldCounts::new(self.race_year);
+        for chunk in chunks.by_ref() {
+            let chunk = chunk?;
+            let own_index_list = chunk.own_indexes().collect::<Vec<_>>();
+            for index in own_index_list.iter() {
+                for token in chunk.parsed.results_as_tokens[*index].iter() {
+                    *token_to_line_count.entry(*token).or_insert(0) += 1;
+                }
+            }
+            let own_fields_list = own_index_list
+                .iter()
+                .map(|index| chunk.parsed.fields_list[*index].clone())
+                .collect::<Vec<_>>();
+            field_counts.add(&own_fields_list);
+            results_count += own_index_list.len();
+        }
+        Ok(TokenCounts {
+            results_count,
+            token_to_line_count,
+            field_counts,
+            encoding: chunks.line_iter.encoding(),
+        })
+    }
+
+    // The second pass. `on_match` gets each result line with a likely member (as in
+    // `GroupBy::Line`), in file order. Returns how many lines matched.
+    pub fn stream_matches<R: BufRead>(
+        &self,
+        member_index: &MemberIndex,
+        token_counts: &TokenCounts,
+        reader: R,
+        include_city: IncludeCity,
+        mut on_match: impl FnMut(LineReport),
+    ) -> Result<usize, anyhow::Error> {
+        self.assert_that_config_is_valid();
+        let include_city_as_bool = match include_city {
+            IncludeCity::Yes => true,
+            IncludeCity::No => false,
+            IncludeCity::Auto => {
+                anyhow::bail!("Streaming needs include city 'yes' or 'no', not 'auto'")
+            }
+        };
+        if self.assignment == Assignment::OneToOne {
+            anyhow::bail!("Streaming can't assign one-to-one");
+        }
+        if self.estimate_prob_member_in_race {
+            anyhow::bail!("Streaming can't estimate prob_member_in_race");
+        }
+
+        let results_count = self.results_count(token_counts.results_count);
+        let (name_stop_words, city_stop_words, city_to_coincidence) = self.find_stop_words(
+            &token_counts.token_to_line_count,
+            results_count,
+            &member_index.name_to_coincidence,
+        );
+        let person_list = member_index.person_list(include_city_as_bool);
+        let token_to_person_list =
+            self.index_person_list(person_list, &name_stop_words, &city_stop_words);
+        let bib_to_person_list = Self::index_bibs(person_list);
+        let prior_points = log_odds(self.prob_member_in_race / results_count as f32);
+
+        let mut match_count = 0;
+        for chunk in Chunks::new(self, reader) {
+            let chunk = chunk?;
+            let parsed = &chunk.parsed;
+            let race_fields =
+                RaceFields::with_counts(&parsed.fields_list, token_counts.field_counts.clone());
+            let line_points_list = self.find_matching_people_for_each_result_line(
+                &parsed.records.line_list,
+                &parsed.results_as_tokens,
+                &parsed.column_tokens_list,
+                &token_to_person_list,
+                &bib_to_person_list,
+                &member_index.name_to_coincidence,
+                &city_to_coincidence,
+                &race_fields,
+            );
+            let start_list = &parsed.records.start_list;
+            let mut line_people_list = self
+                .find_line_people_list(&line_points_list, prior_points)
+                .into_iter()
+                .filter(|line_people| start_list[line_people.line_index] >= chunk.carried)
+                .collect::<Vec<_>>();
+            for line_people in line_people_list.iter_mut() {
+                line_people.fields = parsed.fields_list[line_people.line_index].clone();
+                line_people.line_index =
+                    chunk.start + start_list[line_people.line_index] - chunk.carried;
+            }
+            line_people_list.sort_by_key(|line_people| line_people.line_index);
+            match_count += line_people_list.len();
+            report::line_reports(&line_people_list)
+                .into_iter()
+                .for_each(&mut on_match);
+        }
+        Ok(match_count)
+    }
+
+    // Both passes, opening the results once for each
+    pub fn find_matches_streaming<R: BufRead>(
+        &self,
+        member_index: &MemberIndex,
+        mut open_results: impl FnMut() -> io::Result<R>,
+        include_city: IncludeCity,
+        on_match: impl FnMut(LineReport),
+    ) -> Result<usize, anyhow::Error> {
+        let token_counts = self.count_tokens(member_index, open_results()?)?;
+        self.stream_matches(
+            member_index,
+            &token_counts,
+            open_results()?,
+            include_city,
+            on_match,
+        )
+    }
+}
@@ -0,0 +1,275 @@
+// Sweep the probability threshold over labelled predictions (see `evaluate`) and draw the
+// ROC and precision-recall curves. The "flagged" column is the number of pairs someone
+// would have to check by hand at that threshold.
+
+use core::fmt::Write;
+
+use itertools::Itertools;
+
+use crate::evaluate::{Prediction, ThresholdStats};
+
+const PANEL_SIZE: f32 = 360.0;
+const PANEL_MARGIN: f32 = 60.0;
+
+#[derive(Clone, Debug)]
+pub struct CurvePoint {
+    pub stats: ThresholdStats,
+    pub true_negatives: usize,
+}
+
+impl CurvePoint {
+    pub fn flagged(&self) -> usize {
+        self.stats.true_positives + self.stats.false_positives
+    }
+
+    pub fn true_positive_rate(&self) -> f32 {
+        self.stats.recall()
+    }
+
+    pub fn false_positive_rate(&self) -> f32 {
+        let negatives = self.stats.false_positives + self.true_negatives;
+        if negatives == 0 {
+            0.0
+        } else {
+            self.stats.false_positives as f32 / negatives as f32
+        }
+    }
+}
+
+// One point per distinct predicted probability (highest first), then a last point at
+// threshold 0.0 where every pair, reported or not, is flagged.
+pub fn sweep(prediction_list: &[Prediction], pair_count: usize) -> Vec<CurvePoint> {
+    let positives = prediction_list.iter().filter(|p| p.is_match).count();
+    let negatives = pair_count.saturating_sub(positives);
+    let point = |threshold: f32, true_positives: usize, false_positives: usize| CurvePoint {
+        stats: ThresholdStats {
+            threshold,
+            true_positives,
+            false_positives,
+            false_negatives: positives - true_positives,
+        },
+        true_negatives: negatives.saturating_sub(false_positives),
+    };
+
+    let mut point_list = Vec::new();
+    let (mut true_positives, mut false_positives) = (0, 0);
+    let reported = prediction_list
+        .iter()
+        .filter(|prediction| prediction.prob > 0.0)
+        .sorted_by(|a, b| b.prob.partial_cmp(&a.prob).unwrap());
+    for (_, group) in &reported.group_by(|prediction| prediction.prob.to_bits()) {
+        let mut threshold = 0.0;
+        for prediction in group {
+            threshold = prediction.prob;
+            if prediction.is_match {
+                true_positives += 1;
+            } else {
+                false_positives += 1;
+            }
+        }
+        point_list.push(point(threshold, true_positives, false_positives));
+    }
+    point_list.push(point(0.0, positives, negatives));
+    point_list
+}
+
+pub fn to_csv(point_list: &[CurvePoint]) -> String {
+    let mut csv = String::from(
+        "threshold,flagged,true_positives,false_positives,false_negatives,true_negatives,precision,recall,false_positive_rate\n",
+    );
+    for point in point_list {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{:.4},{:.4},{:.6}",
+            point.stats.threshold,
+            point.flagged(),
+            point.stats.true_positives,
+            point.stats.false_positives,
+            point.stats.false_negatives,
+            point.true_negatives,
+            point.stats.precision(),
+            point.stats.recall(),
+            point.false_positive_rate()
+        )
+        .unwrap(); // writing to a String can't fail
+    }
+    csv
+}
+
+// Two side-by-side charts. The ROC chart uses a log false-positive-rate axis because nearly
+// every (member, result line) pair is a true negative.
+pub fn to_svg(point_list: &[CurvePoint]) -> String {
+    let min_rate = point_list
+        .iter()
+        .map(|point| point.false_positive_rate())
+        .filter(|rate| *rate > 0.0)
+        .fold(1.0f32, f32::min)
+        .min(0.1);
+    let min_decade = min_rate.log10().floor();
+    let log_x = |rate: f32| (rate.max(min_rate).log10() - min_decade) / -min_decade;
+
+    let mut roc = vec![(0.0, 0.0)];
+    roc.extend(point_list.iter().map(|point| {
+        (
+            log_x(point.false_positive_rate()),
+            point.true_positive_rate(),
+        )
+    }));
+    let roc_ticks = (min_decade as i32..=0)
+        .map(|decade| (log_x(10f32.powi(decade)), format!("1e{decade}")))
+        .collect_vec();
+
+    let pr = point_list
+        .iter()
+        .filter(|point| point.flagged() > 0)
+        .map(|point| (point.stats.recall(), point.stats.precision()))
+        .collect_vec();
+    let linear_ticks = (0..=4)
+        .map(|i| (i as f32 / 4.0, format!("{:.2}", i as f32 / 4.0)))
+        .collect_vec();
+
+    let width = 2.0 * (PANEL_SIZE + 2.0 * PANEL_MARGIN);
+    let height = PANEL_SIZE + 2.0 * PANEL_MARGIN;
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" font-family=\"sans-serif\" font-size=\"12\">
+{roc}
+{pr}
+</svg>
+",
+        roc = panel(
+            0.0,
+            "ROC",
+            "false positive rate (log)",
+            "true positive rate",
+            &roc,
+            &roc_ticks,
+            &linear_ticks
+        ),
+        pr = panel(
+            PANEL_SIZE + 2.0 * PANEL_MARGIN,
+            "Precision-Recall",
+            "recall",
+            "precision",
+            &pr,
+            &linear_ticks,
+            &linear_ticks
+        ),
+    )
+}
+
+// x and y are already scaled to 0.0..=1.0
+fn panel(
+    offset: f32,
+    title: &str,
+    x_label: &str,
+    y_label: &str,
+    xy_list: &[(f32, f32)],
+    x_ticks: &[(f32, String)],
+    y_ticks: &[(f32, String)],
+) -> String {
+    let left = offset + PANEL_MARGIN;
+    let top = PANEL_MARGIN;
+    let bottom = top + PANEL_SIZE;
+    let to_x = |x: f32| left + x * PANEL_SIZE;
+    let to_y = |y: f32| bottom - y * PANEL_SIZE;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"16\">{title}</text>",
+        to_x(0.5),
+        top - 20.0
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "<rect x=\"{left}\" y=\"{top}\" width=\"{PANEL_SIZE}\" height=\"{PANEL_SIZE}\" fill=\"none\" stroke=\"black\"/>"
+    )
+    .unwrap();
+    for (x, label) in x_ticks {
+        writeln!(
+            svg,
+            "<line x1=\"{x}\" y1=\"{bottom}\" x2=\"{x}\" y2=\"{}\" stroke=\"black\"/><text x=\"{x}\" y=\"{}\" text-anchor=\"middle\">{label}</text>",
+            bottom + 5.0,
+            bottom + 18.0,
+            x = to_x(*x)
+        )
+        .unwrap();
+    }
+    for (y, label) in y_ticks {
+        writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{y}\" x2=\"{left}\" y2=\"{y}\" stroke=\"black\"/><text x=\"{}\" y=\"{}\" text-anchor=\"end\">{label}</text>",
+            left - 5.0,
+            left - 8.0,
+            to_y(*y) + 4.0,
+            y = to_y(*y)
+        )
+        .unwrap();
+    }
+    writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{x_label}</text>",
+        to_x(0.5),
+        bottom + 40.0
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "<text x=\"{x}\" y=\"{y}\" text-anchor=\"middle\" transform=\"rotate(-90 {x} {y})\">{y_label}</text>",
+        x = left - 45.0,
+        y = to_y(0.5)
+    )
+    .unwrap();
+    let points = xy_list
+        .iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", to_x(*x), to_y(*y)))
+        .join(" ");
+    writeln!(
+        svg,
+        "<polyline points=\"{points}\" fill=\"none\" stroke=\"steelblue\" stroke-width=\"2\"/>"
+    )
+    .unwrap();
+    svg
+}
+
+// A self-contained page: the chart plus the table behind it.
+pub fn to_html(point_list: &[CurvePoint]) -> String {
+    let rows = point_list
+        .iter()
+        .map(|point| {
+            format!(
+                "<tr><td>{:.4}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.3}</td><td>{:.3}</td></tr>",
+                point.stats.threshold,
+                point.flagged(),
+                point.stats.true_positives,
+                point.stats.false_positives,
+                point.stats.precision(),
+                point.stats.recall()
+            )
+        })
+        .join("\n");
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Threshold sweep</title>
+<style>
+table {{ border-collapse: collapse; }}
+td, th {{ border: 1px solid #ccc; padding: 2px 8px; text-align: right; }}
+</style>
+</head>
+<body>
+<h1>Threshold sweep</h1>
+{svg}
+<table>
+<tr><th>threshold</th><th>flagged</th><th>right</th><th>wrong</th><th>precision</th><th>recall</th></tr>
+{rows}
+</table>
+</body>
+</html>
+",
+        svg = to_svg(point_list)
+    )
+}
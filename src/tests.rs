@@ -4,11 +4,19 @@
 use std::fs::File;
 use std::io::Write;
 
+use crate::evidence::RaceFields;
+use crate::fields::{format_time, parse_result_fields, Gender};
+use crate::generate::{Generator, ResultLayout};
+use crate::intern::Lookup;
+use crate::layout::Layout;
+use crate::scoring::{PointsTable, Scoring};
 use crate::{
-    delta_many_names, delta_one, delta_one_name, log_odds, prob, read_lines, Config, IncludeCity,
-    Token, TokenToCoincidence, SAMPLE_MEMBERS_STR, SAMPLE_RESULTS_STR,
+    delta_many_names, delta_one, delta_one_name, evaluate, log_odds, prob, read_lines, scoring,
+    season, sweep, Assignment, Config, GroupBy, IncludeCity, OutputFormat, Token,
+    TokenToCoincidence, SAMPLE_MEMBERS_STR, SAMPLE_RESULTS_STR,
 };
 use anyhow::anyhow;
+use itertools::Itertools;
 
 #[allow(clippy::format_in_format_args)]
 #[test]
@@ -417,3 +425,1620 @@ fn missing_names() {
     }
     assert_eq!(matches.len(), 2);
 }
+
+#[test]
+fn estimate_prob_member_in_race() -> anyhow::Result<()> {
+    // Every member is in the race, so the estimate should be much higher than the default 1%
+    let member_lines =
+        "Deena\tKastor\tMammoth Lakes\nMagdalena\tLewy Boulet\tOakland\nBlake\tRussell\tPacific Grove\nTera\tMoody\tBoulder\n"
+            .lines()
+            .map(String::from)
+            .collect_vec();
+    let config = Config {
+        estimate_prob_member_in_race: true,
+        ..Config::default()
+    };
+    let (estimate, _) =
+        config.find_line_people(&member_lines, &result_lines(), IncludeCity::Yes)?;
+    assert!(estimate > 0.5, "{estimate}");
+
+    // The report gives the same estimate
+    let matches = config.find_matches(&member_lines, result_lines(), IncludeCity::Yes)?;
+    assert_eq!(
+        matches[0],
+        format!(
+            "<p>Estimated prob_member_in_race: {:.2}%</p>",
+            estimate * 100.0
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn evaluate_cases() -> anyhow::Result<()> {
+    let case_dir = std::env::temp_dir().join("race_results_evaluate_cases/olympic_trials");
+    std::fs::create_dir_all(&case_dir)?;
+    std::fs::write(
+        case_dir.join("members.txt"),
+        "Deena\tKastor\tMammoth Lakes\nTera\tMoody\tBoulder\nChellie\tPingree\tSeattle\n",
+    )?;
+    std::fs::write(case_dir.join("results.txt"), SAMPLE_RESULTS_STR.as_bytes())?;
+    // Deena Kastor is on line 6 and Tera Moody is on line 10
+    std::fs::write(
+        case_dir.join("labels.tsv"),
+        "# member\tresult\n1\t6\n2\t10\n",
+    )?;
+
+    let case_list = evaluate::load_cases(case_dir.parent().unwrap())?;
+    assert_eq!(case_list.len(), 1);
+    assert_eq!(case_list[0].labels.len(), 2);
+
+    let report = evaluate::evaluate(
+        &Config::default(),
+        &case_list,
+        IncludeCity::Yes,
+        &evaluate::DEFAULT_THRESHOLDS,
+    )?;
+    println!("{report}");
+    let stats = &report.threshold_stats_list[4];
+    assert_eq!(stats.threshold, 0.5);
+    assert_eq!(stats.recall(), 1.0);
+    assert_eq!(report.calibration.len(), 10);
+    Ok(())
+}
+
+#[test]
+fn evaluate_pair_count() -> anyhow::Result<()> {
+    // Header rows and blank lines aren't members or results, so they make no pairs
+    let case = evaluate::Case {
+        name: "pairs".to_string(),
+        member_lines: [
+            "First\tLast\tCity",
+            "Deena\tKastor\tBoulder",
+            "Tera\tMoody\tBoulder",
+        ]
+        .map(String::from)
+        .to_vec(),
+        result_lines: [
+            "Place\tBib\tName\tAge\tHometown\tFinish",
+            "1\t1\tDeena Kastor\t35\tBoulder\t2:29:35",
+            "",
+            "2\t2\tTera Moody\t27\tBoulder\t2:30:19",
+            "3\t3\tZoila Gomez\t28\tAlamosa\t2:33:53",
+        ]
+        .map(String::from)
+        .to_vec(),
+        labels: [(1, 1), (2, 3)].into(),
+    };
+    let report = evaluate::evaluate(&Config::default(), &[case], IncludeCity::Yes, &[0.5])?;
+    assert_eq!(report.pair_count, 2 * 3);
+    Ok(())
+}
+
+#[test]
+fn bad_labels() {
+    let label_lines = vec!["1\t6".to_string(), "0\t2".to_string()];
+    assert_eq!(
+        evaluate::parse_labels(&label_lines).map_err(|e| e.to_string()),
+        Err("Label line numbers start at 1, not '0\t2'".to_string())
+    );
+}
+
+#[test]
+fn threshold_sweep() {
+    let prediction = |prob: f32, is_match: bool| evaluate::Prediction {
+        case: "case".to_string(),
+        member_index: 0,
+        line_index: 0,
+        member_line: String::new(),
+        result_line: String::new(),
+        prob,
+        is_match,
+    };
+    let prediction_list = vec![
+        prediction(0.9, true),
+        prediction(0.8, false),
+        prediction(0.8, true),
+        prediction(0.3, false),
+        prediction(0.0, true),
+    ];
+    let point_list = sweep::sweep(&prediction_list, 100);
+    let summary = point_list
+        .iter()
+        .map(|point| {
+            (
+                point.stats.threshold,
+                point.stats.true_positives,
+                point.stats.false_positives,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![(0.9, 1, 0), (0.8, 2, 1), (0.3, 2, 2), (0.0, 3, 97)]
+    );
+    assert_eq!(point_list[1].flagged(), 3);
+    assert_eq!(point_list[3].false_positive_rate(), 1.0);
+
+    let csv = sweep::to_csv(&point_list);
+    assert_eq!(csv.lines().count(), 5);
+    assert!(sweep::to_html(&point_list).contains("<polyline"));
+}
+
+#[test]
+fn generate_race() -> anyhow::Result<()> {
+    for layout in [
+        ResultLayout::Tab,
+        ResultLayout::Csv,
+        ResultLayout::FixedWidth,
+    ] {
+        let race = Generator {
+            member_count: 50,
+            result_count: 200,
+            members_in_race: 10,
+            layout,
+            ..Generator::default()
+        }
+        .generate()?;
+        assert_eq!(race.member_lines.len(), 50);
+        assert_eq!(race.result_lines.len(), 201); // plus a header
+        assert_eq!(race.labels.len(), 10);
+    }
+
+    let race_dir = std::env::temp_dir().join("race_results_generate_race/case");
+    Generator::default().generate()?.write(&race_dir)?;
+    let case = evaluate::Case::load(&race_dir)?;
+    assert_eq!(case.labels.len(), Generator::default().members_in_race);
+    Ok(())
+}
+
+// The NYC Marathon has about 50,000 finishers
+#[test]
+fn generate_big_race() -> anyhow::Result<()> {
+    let race = Generator {
+        member_count: 500,
+        result_count: 50_000,
+        members_in_race: 50,
+        ..Generator::default()
+    }
+    .generate()?;
+    let case = evaluate::Case {
+        name: "big".to_string(),
+        member_lines: race.member_lines,
+        result_lines: race.result_lines,
+        labels: race.labels.into_iter().collect(),
+    };
+    let report = evaluate::evaluate(&Config::default(), &[case], IncludeCity::Yes, &[0.5])?;
+    println!("{report}");
+    assert!(report.threshold_stats_list[0].true_positives > 0);
+    Ok(())
+}
+
+#[test]
+fn one_to_one() -> anyhow::Result<()> {
+    let member_lines = ["Rick\tAllen\tSammamish", "Rick\tAllen Jr\tSammamish"]
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>();
+    let mut result_lines = SAMPLE_RESULTS_STR
+        .lines()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>();
+    result_lines.push("400\t1\tRick Allen\t52\tSammamish, WA\t3:01:00".to_string());
+    result_lines.push("401\t2\tRick Allen Jr\t24\tSammamish, WA\t3:01:05".to_string());
+
+    let pair_count =
+        |member_lines: &[String], assignment: Assignment| -> anyhow::Result<(usize, usize)> {
+            let config = Config {
+                assignment,
+                ..Config::default()
+            };
+            let (_, line_people_list) =
+                config.find_line_people(member_lines, &result_lines, IncludeCity::Yes)?;
+            let pair_list = line_people_list
+                .iter()
+                .flat_map(|line_people| {
+                    line_people
+                        .person_prob_list
+                        .iter()
+                        .map(|person_prob| (line_people.line_index, person_prob.person.id))
+                })
+                .collect::<Vec<_>>();
+            let distinct_ids = pair_list.iter().map(|(_, id)| *id).unique().count();
+            Ok((pair_list.len(), distinct_ids))
+        };
+
+    let (independent_pairs, _) = pair_count(&member_lines, Assignment::Independent)?;
+    assert!(independent_pairs > 2);
+    // each member once, each line once
+    assert_eq!(pair_count(&member_lines, Assignment::OneToOne)?, (2, 2));
+
+    // Rick Allen on Rick Allen Jr's line is flagged (the name matches, but the bib doesn't), but
+    // a pair the assignment rejected isn't reported, flagged or not
+    let member_lines = [
+        "First\tLast\tCity\tBib",
+        "Rick\tAllen\tSammamish\t1",
+        "Rick\tAllen Jr\tSammamish\t2",
+    ]
+    .map(String::from);
+    assert_eq!(pair_count(&member_lines, Assignment::OneToOne)?, (2, 2));
+    Ok(())
+}
+
+#[test]
+fn group_by_member() -> anyhow::Result<()> {
+    let member_lines = "Rick\tAllen\tSammamish\nDeena\tKastor\tMammoth Lakes\n".lines();
+    let result_lines = SAMPLE_RESULTS_STR
+        .lines()
+        .chain(["400\t1\tRick Allen\t52\tSammamish, WA\t3:01:00"])
+        .chain(["401\t2\tRick Allen\t24\tSammamish, WA\t3:01:05"]);
+    let config = Config {
+        group_by: GroupBy::Member,
+        output_format: OutputFormat::Json,
+        ..Config::default()
+    };
+    let matches =
+        config.find_matches(member_lines.clone(), result_lines.clone(), IncludeCity::Yes)?;
+    assert_eq!(matches.len(), 1);
+    let json: serde_json::Value = serde_json::from_str(&matches[0])?;
+    let members = json["members"].as_array().unwrap();
+    let rick = members
+        .iter()
+        .find(|member| member["member_number"] == 1)
+        .unwrap();
+    let rick_lines = rick["lines"].as_array().unwrap();
+    let full_name_count = rick_lines
+        .iter()
+        .filter(|line| line["line"].as_str().unwrap().contains("Rick Allen"))
+        .count();
+    assert_eq!(full_name_count, 2);
+    assert!(rick_lines[..2]
+        .iter()
+        .all(|line| line["line"].as_str().unwrap().contains("Rick Allen")));
+    assert_eq!(rick["needs_review"], true);
+    let deena = members
+        .iter()
+        .find(|member| member["member_number"] == 2)
+        .unwrap();
+    assert_eq!(deena["needs_review"], false);
+
+    let text = Config {
+        group_by: GroupBy::Member,
+        output_format: OutputFormat::Text,
+        ..Config::default()
+    }
+    .find_matches(member_lines, result_lines, IncludeCity::Yes)?;
+    assert!(text.iter().any(|line| line.contains("[review")));
+    Ok(())
+}
+
+#[test]
+fn season_batch() -> anyhow::Result<()> {
+    let member_lines = ["Rick\tAllen\tSammamish", "Deena\tKastor\tMammoth Lakes"]
+        .map(String::from)
+        .to_vec();
+    let race = |name: &str, extra: &[&str]| season::Race {
+        name: name.to_string(),
+        date: "2023-06-01".to_string(),
+        result_lines: SAMPLE_RESULTS_STR
+            .lines()
+            .chain(extra.iter().copied())
+            .map(String::from)
+            .collect(),
+        encoding: None,
+    };
+    let race_list = [
+        race(
+            "Spring",
+            &["400\t1\tRick Allen\t52\tSammamish, WA\t3:01:00"],
+        ),
+        race("Summer", &[]),
+        race("Fall", &["401\t2\tRick Allen\t52\tSammamish, WA\t3:02:00"]),
+    ];
+    let season_report = season::find_season_matches(
+        &Config::default(),
+        &member_lines,
+        &race_list,
+        IncludeCity::Yes,
+        0.5,
+    )?;
+    assert_eq!(season_report.races.len(), 3);
+    let rick = season_report
+        .members
+        .iter()
+        .find(|season| season.member_number == 1)
+        .unwrap();
+    assert_eq!(rick.race_count, 2);
+    assert_eq!(
+        rick.races.iter().map(|r| r.race.as_str()).collect_vec(),
+        ["Spring", "Fall"]
+    );
+    assert!(season_report.to_html().contains("Season summary"));
+    Ok(())
+}
+
+#[test]
+fn result_fields() {
+    let result_lines = SAMPLE_RESULTS_STR.lines().map(String::from).collect_vec();
+    let fields_list = parse_result_fields(&result_lines);
+    let deena = &fields_list[5];
+    assert_eq!(deena.place, Some(1));
+    assert_eq!(deena.time, Some((2 * 60 + 29) as f32 * 60.0 + 35.0));
+    assert_eq!(deena.gender, Some(Gender::Female));
+    assert_eq!(deena.bib.as_deref(), Some("1"));
+    assert_eq!(deena.age, Some(35));
+    assert_eq!(fields_list[4].place, None); // the header
+    let last = fields_list.iter().rfind(|fields| fields.place.is_some());
+    assert_eq!(last.unwrap().gender, Some(Gender::Male));
+
+    let fields = crate::fields::ResultFields::parse("12  Smith, Bob  M40-44  16:02.4");
+    assert_eq!(fields.place, Some(12));
+    assert_eq!(fields.gender, Some(Gender::Male));
+    assert_eq!(fields.time.map(format_time), Some("16:02.4".to_string()));
+}
+
+#[test]
+fn club_scoring() -> anyhow::Result<()> {
+    let member_lines = [
+        "Deena\tKastor\tMammoth Lakes",
+        "Blake\tRussell\tPacific Grove",
+        "Tera\tMoody\tBoulder",
+        "Ryan\tHall\tMammoth Lakes",
+    ]
+    .map(String::from)
+    .to_vec();
+    let race_list = [season::Race {
+        name: "Trials".to_string(),
+        date: "2008-04-20".to_string(),
+        result_lines: SAMPLE_RESULTS_STR.lines().map(String::from).collect(),
+        encoding: None,
+    }];
+    let season_report = season::find_season_matches(
+        &Config::default(),
+        &member_lines,
+        &race_list,
+        IncludeCity::Yes,
+        0.5,
+    )?;
+    let scoring = Scoring {
+        points_table: "10,8".parse::<PointsTable>()?,
+        team_size: 3,
+        ..Scoring::default()
+    };
+    let standings = scoring::score(&scoring, &season_report, &race_list);
+    let points = |number: usize| {
+        standings
+            .members
+            .iter()
+            .find(|standing| standing.member_number == number)
+            .map(|standing| standing.points)
+    };
+    assert_eq!(points(1), Some(10)); // Deena, 1st woman
+    assert_eq!(points(2), Some(8)); // Blake, 3rd woman
+    assert_eq!(points(3), Some(1)); // Tera, past the points table
+    assert_eq!(points(4), Some(10)); // Ryan, 1st man
+
+    let women = &standings.races[0].teams[0];
+    assert_eq!(women.gender, Some(Gender::Female));
+    assert_eq!(women.score, 1 + 3 + 5);
+    assert!(women.complete);
+    assert!(!standings.races[0].teams[1].complete);
+    assert!(standings.to_csv().starts_with("rank,"));
+    assert!("10,x".parse::<PointsTable>().is_err());
+    Ok(())
+}
+
+#[test]
+fn html_is_escaped() -> anyhow::Result<()> {
+    let member_lines = ["Deena\tKastor\tMammoth & Lakes".to_string()];
+    let result_lines = ["1 Deena Kastor <b>Mammoth Lakes</b> 2:29:35".to_string()];
+    let html = Config::default()
+        .find_matches(&member_lines, &result_lines, IncludeCity::Yes)?
+        .join("\n");
+    assert!(html.contains("<pre>1 Deena Kastor &lt;b&gt;Mammoth Lakes&lt;/b&gt; 2:29:35</pre>"));
+    assert!(html.contains("&amp;") && !html.contains("<b>"));
+
+    let html = Config {
+        group_by: GroupBy::Member,
+        ..Config::default()
+    }
+    .find_matches(&member_lines, &result_lines, IncludeCity::Yes)?
+    .join("\n");
+    assert!(html.contains("Mammoth &amp; Lakes") && !html.contains("<b>"));
+
+    let race_list = [season::Race {
+        name: "Trails & <Tears>".to_string(),
+        date: "2023-06-01".to_string(),
+        result_lines: result_lines.to_vec(),
+        encoding: None,
+    }];
+    let season_report = season::find_season_matches(
+        &Config::default(),
+        &member_lines,
+        &race_list,
+        IncludeCity::Yes,
+        0.5,
+    )?;
+    let standings = scoring::score(&Scoring::default(), &season_report, &race_list);
+    for html in [season_report.to_html(), standings.to_html()] {
+        assert!(html.contains("Trails &amp; &lt;Tears&gt;"));
+        assert!(html.contains("Mammoth &amp; Lakes"));
+        assert!(!html.contains("<Tears>") && !html.contains("<b>"));
+    }
+    Ok(())
+}
+
+#[test]
+fn club_scoring_checks_members() -> anyhow::Result<()> {
+    // Ryan is a woman in the members file, and Deena is in it twice
+    let member_lines = [
+        "First\tLast\tCity\tGender",
+        "Deena\tKastor\tMammoth Lakes\tF",
+        "Deena\tKastor\tMammoth Lakes\tF",
+        "Ryan\tHall\tMammoth Lakes\tF",
+    ]
+    .map(String::from)
+    .to_vec();
+    let race_list = [season::Race {
+        name: "Trials".to_string(),
+        date: "2008-04-20".to_string(),
+        result_lines: SAMPLE_RESULTS_STR.lines().map(String::from).collect(),
+        encoding: None,
+    }];
+    let season_report = season::find_season_matches(
+        &Config::default(),
+        &member_lines,
+        &race_list,
+        IncludeCity::Yes,
+        0.5,
+    )?;
+    assert_eq!(season_report.members.len(), 3);
+    let standings = scoring::score(&Scoring::default(), &season_report, &race_list);
+    let finishers = &standings.races[0].finishers;
+    assert_eq!(
+        finishers
+            .iter()
+            .map(|finisher| finisher.member.as_str())
+            .collect_vec(),
+        ["Deena Kastor @ Mammoth Lakes"]
+    );
+    Ok(())
+}
+
+#[test]
+fn result_fields_with_header() -> anyhow::Result<()> {
+    let result_lines = [
+        "Pl\tName\tDiv\tBib\tGun Time\tChip Time",
+        "1\tPat Smith\tF40-44\t317\t1:02:11\t1:01:58.3",
+        "2\tLee Jones\t\t12\t1:03:00\t",
+    ]
+    .map(String::from);
+    let fields_list = parse_result_fields(&result_lines);
+    let pat = &fields_list[1];
+    assert_eq!(pat.place, Some(1));
+    assert_eq!(pat.bib.as_deref(), Some("317"));
+    assert_eq!(pat.division.as_deref(), Some("F40-44"));
+    assert_eq!(pat.gender, Some(Gender::Female));
+    assert_eq!(pat.gun_time.map(format_time), Some("1:02:11".to_string()));
+    assert_eq!(pat.time.map(format_time), Some("1:01:58.3".to_string()));
+    let lee = &fields_list[2];
+    assert_eq!((lee.division.as_deref(), lee.chip_time), (None, None));
+    assert_eq!(lee.time, lee.gun_time);
+
+    // Without a header, the shorter of two finish times is the chip time
+    let fields = crate::fields::ResultFields::parse("7 Pat Smith 45:10 44:52 7:14");
+    assert_eq!(fields.chip_time.map(format_time), Some("44:52".to_string()));
+    assert_eq!(fields.gun_time.map(format_time), Some("45:10".to_string()));
+
+    // The fields ride along with each match in the JSON
+    let config = Config {
+        output_format: OutputFormat::Json,
+        ..Config::default()
+    };
+    let matches = config.find_matches(
+        ["Deena\tKastor\tMammoth Lakes"],
+        SAMPLE_RESULTS_STR.lines(),
+        IncludeCity::Yes,
+    )?;
+    let json: serde_json::Value = serde_json::from_str(&matches[0])?;
+    let fields = &json["lines"][0]["fields"];
+    assert_eq!(fields["place"], 1);
+    assert_eq!(fields["time"], 2.0 * 3600.0 + 29.0 * 60.0 + 35.0);
+    assert_eq!(fields["gender"], "Female");
+    Ok(())
+}
+
+#[test]
+fn birth_year_evidence() -> anyhow::Result<()> {
+    // A father and son with the same name and city
+    let member_lines = [
+        "First\tLast\tCity\tBirth Year",
+        "Bob\tSmith\tSeattle\t1961",
+        "Bob\tSmith\tSeattle\t1993",
+    ]
+    .map(String::from)
+    .to_vec();
+    let result_lines = SAMPLE_RESULTS_STR
+        .lines()
+        .chain(["300\t101\tBob Smith\t62\tSeattle, WA\t3:30:00"])
+        .map(String::from)
+        .collect_vec();
+    let line_index = result_lines.len() - 1;
+    // Member ids are line indexes in the members file, so 1 is the father and 2 the son
+    let probs = |config: &Config| -> anyhow::Result<Vec<(usize, f32)>> {
+        let (_, line_people_list) =
+            config.find_line_people(&member_lines, &result_lines, IncludeCity::Yes)?;
+        let line_people = line_people_list
+            .iter()
+            .find(|line_people| line_people.line_index == line_index)
+            .unwrap();
+        Ok(line_people
+            .person_prob_list
+            .iter()
+            .map(|person_prob| (person_prob.person.id, person_prob.prob))
+            .sorted_by_key(|(id, _)| *id)
+            .collect())
+    };
+
+    // Without a race year, they look the same
+    let without = probs(&Config::default())?;
+    assert_eq!(without[0].1, without[1].1);
+
+    let with = probs(&Config {
+        race_year: Some(2023),
+        ..Config::default()
+    })?;
+    assert_eq!((with[0].0, with[1].0), (1, 2));
+    assert!(with[0].1 > 0.9 && with[1].1 < without[1].1, "{with:?}");
+
+    let bad = ["Bob\tSmith\tSeattle\tsixty"].map(String::from);
+    assert!(Config::default()
+        .find_line_people(&bad, &result_lines, IncludeCity::Yes)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn comma_separated_only_when_csv_like() {
+    // A comma in the city doesn't make a line comma-separated, even on every line
+    let result_lines = [
+        "12 Jane Doe Seattle, WA 25:01",
+        "13 John Smith Portland, OR 25:40",
+    ]
+    .map(String::from);
+    let fields_list = parse_result_fields(&result_lines);
+    assert_eq!(fields_list[0].place, Some(12));
+    assert_eq!(
+        fields_list[0].time.map(format_time),
+        Some("25:01".to_string())
+    );
+
+    // Lines with the same commas are
+    let result_lines =
+        ["12,Jane Doe,Seattle,25:01", "13,John Smith,Portland,25:40"].map(String::from);
+    let fields_list = parse_result_fields(&result_lines);
+    assert_eq!(fields_list[1].place, Some(13));
+    assert_eq!(
+        fields_list[1].time.map(format_time),
+        Some("25:40".to_string())
+    );
+}
+
+#[test]
+fn bib_is_not_division() {
+    let fields = crate::fields::ResultFields::parse("12 F123 Jane Doe 40 25:01");
+    assert_eq!(fields.gender, None);
+    assert_eq!(fields.division, None);
+
+    for division in ["F35-39", "F9", "F40+"] {
+        let fields = crate::fields::ResultFields::parse(&format!("12 Jane Doe {division} 25:01"));
+        assert_eq!(fields.gender, Some(Gender::Female));
+    }
+}
+
+#[test]
+fn middle_initial_is_not_gender() -> anyhow::Result<()> {
+    let result_line = "1 Jane M Doe 40 Seattle 40:00";
+    let fields = crate::fields::ResultFields::parse(result_line);
+    assert_eq!(fields.gender, None);
+    assert_eq!(fields.division, None);
+
+    // So a woman's result isn't held against her
+    let result_lines = [result_line.to_string()];
+    let delta_of = |member_line: &str| -> anyhow::Result<f32> {
+        let (_, line_people_list) = Config::default().find_line_people(
+            &[member_line.to_string()],
+            &result_lines,
+            IncludeCity::Yes,
+        )?;
+        Ok(line_people_list[0].person_prob_list[0].delta)
+    };
+    assert_eq!(
+        delta_of("Jane\tDoe\tSeattle\t\tF")?,
+        delta_of("Jane\tDoe\tSeattle")?
+    );
+    Ok(())
+}
+
+#[test]
+fn gender_evidence() -> anyhow::Result<()> {
+    // Same name and city, one in each section
+    let result_lines = [
+        "WOMEN",
+        "1\tPat Lee\tSeattle\t40:00",
+        "MEN",
+        "1\tPat Lee\tSeattle\t35:00",
+    ]
+    .iter()
+    .chain(SAMPLE_RESULTS_STR.lines().collect_vec().iter())
+    .map(|line| line.to_string())
+    .collect_vec();
+    let line_to_prob = |member_lines: &[&str]| -> anyhow::Result<Vec<(usize, f32)>> {
+        let member_lines = member_lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect_vec();
+        let (_, line_people_list) =
+            Config::default().find_line_people(&member_lines, &result_lines, IncludeCity::Yes)?;
+        Ok(line_people_list
+            .iter()
+            .filter(|line_people| line_people.line_index < 4)
+            .map(|line_people| (line_people.line_index, line_people.max_prob))
+            .sorted_by_key(|(line_index, _)| *line_index)
+            .collect())
+    };
+
+    // A gender column
+    let probs = line_to_prob(&["Pat\tLee\tSeattle\t\tM"])?;
+    assert_eq!(probs[0].0, 1);
+    assert!(probs[0].1 < probs[1].1, "{probs:?}");
+
+    // Without one, a soft guess from the first name
+    let probs = line_to_prob(&["Mary\tLee\tSeattle"])?;
+    assert_eq!((probs[0].0, probs[1].0), (1, 3));
+    assert!(probs[0].1 > probs[1].1, "{probs:?}");
+
+    let bad = ["Pat\tLee\tSeattle\t\tX"];
+    assert!(line_to_prob(&bad).is_err());
+    Ok(())
+}
+
+#[test]
+fn bib_evidence() -> anyhow::Result<()> {
+    let member_lines = [
+        "Deena\tKastor\tMammoth Lakes\t\t\t1",
+        "Magdalena\tLewy Boulet\tOakland\t\t\t999",
+        "Pat\tNobody\tNowhere\t\t\t017",
+    ];
+    let config = Config {
+        output_format: OutputFormat::Json,
+        ..Config::default()
+    };
+    let matches =
+        config.find_matches(member_lines, SAMPLE_RESULTS_STR.lines(), IncludeCity::Yes)?;
+    let json: serde_json::Value = serde_json::from_str(&matches[0])?;
+    let candidate = |line_number: usize, member_number: usize| {
+        json["lines"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|line| line["line_number"] == line_number)
+            .and_then(|line| {
+                line["members"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .find(|member| member["member_number"] == member_number)
+                    .cloned()
+            })
+    };
+
+    // Bib and name agree
+    let deena = candidate(6, 1).unwrap();
+    assert!(deena["prob"].as_f64().unwrap() > 0.99);
+    assert!(deena.get("flags").is_none());
+
+    // The name matches, but the bib doesn't
+    let magdalena = candidate(7, 2).unwrap();
+    assert_eq!(
+        magdalena["flags"][0],
+        "name matches, but bib is 43, not 999"
+    );
+
+    // The bib matches, but the name doesn't, so it's reported even though unlikely
+    let pat = candidate(8, 3).unwrap();
+    assert_eq!(pat["flags"][0], "bib matches, but name doesn't");
+    Ok(())
+}
+
+#[test]
+fn column_aware_tokens() -> anyhow::Result<()> {
+    let member_lines = ["Jack,Bergman,Portland".to_string()];
+    let body = [
+        "1\t101\tAlice Wong\t34\tEugene, OR\t18:02",
+        "2\t102\tJack Smith\t41\tBergman, OR\t18:40",
+        "3\t103\tMaria Lopez\t29\tSalem, OR\t19:15",
+        "4\t104\tTom Baker\t52\tBend, OR\t19:58",
+        "5\t105\tSue Park\t45\tAshland, OR\t20:31",
+        "6\t106\tJack Bergman\t38\tPortland, OR\t21:07",
+    ];
+    let with_header = std::iter::once("Place\tBib\tName\tAge\tHometown\tFinish")
+        .chain(body)
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let without_header = body.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+
+    let config = Config::default();
+    let prob_of = |result_lines: &[String], name: &str| -> anyhow::Result<f32> {
+        let (_, line_people_list) =
+            config.find_line_people(&member_lines, result_lines, IncludeCity::No)?;
+        Ok(line_people_list
+            .iter()
+            .find(|line_people| line_people.line.contains(name))
+            .map_or(0.0, |line_people| line_people.max_prob))
+    };
+
+    // Either way, the real Jack Bergman matches
+    assert!(prob_of(&with_header, "Jack Bergman")? > 0.99);
+    assert!(prob_of(&without_header, "Jack Bergman")? > 0.99);
+
+    // Without a header, the whole line is a bag of tokens, so Jack from Bergman looks like a
+    // full name. With one, "Bergman" in the hometown column isn't part of a name.
+    let bag_prob = prob_of(&without_header, "Jack Smith")?;
+    let column_prob = prob_of(&with_header, "Jack Smith")?;
+    assert!(bag_prob > 0.99, "{bag_prob}");
+    assert!(column_prob < 0.5, "{column_prob}");
+    Ok(())
+}
+
+#[test]
+fn single_spaced_header() -> anyhow::Result<()> {
+    // Split on single spaces, "Kastor" would land in the age column. Instead the line is a bag of
+    // tokens, just as it is without the header.
+    let body = ["1 1 Deena Kastor 35 Boulder 2:29:35"];
+    let with_header = std::iter::once("Place Bib Name Age Hometown Finish")
+        .chain(body)
+        .map(String::from)
+        .collect_vec();
+    let layout = Layout::detect(&with_header);
+    assert!(layout.is_header(0));
+    assert!(layout.columns(1, &with_header[1]).is_none());
+    assert!(layout
+        .column_tokens(&Lookup::new(), 1, &with_header[1])
+        .is_none());
+
+    let member_lines = ["Deena,Kastor,Boulder".to_string()];
+    let config = Config {
+        override_results_count: Some(1000),
+        ..Config::default()
+    };
+    let points_of = |result_lines: &[String]| -> anyhow::Result<f32> {
+        let (_, line_people_list) =
+            config.find_line_people(&member_lines, result_lines, IncludeCity::Yes)?;
+        Ok(line_people_list[0].person_prob_list[0].delta)
+    };
+    let without_header = body.map(String::from);
+    assert_eq!(points_of(&with_header)?, points_of(&without_header)?);
+    Ok(())
+}
+
+#[test]
+fn fixed_width_columns() -> anyhow::Result<()> {
+    // A missing age leaves a blank in its column instead of shifting the hometown over
+    let result_lines = [
+        "Place  Name                 Age  Hometown      Time",
+        "    1  Wong, Alice           34  Eugene        18:02",
+        "    2  Smith, Jack               Bergman       18:40",
+        "   10  Bergman, Jack         38  Portland      21:07",
+    ]
+    .map(String::from);
+    let fields_list = parse_result_fields(&result_lines);
+    assert_eq!(fields_list[1].age, Some(34));
+    assert_eq!(fields_list[2].age, None);
+    assert_eq!(
+        fields_list[2].time.map(format_time),
+        Some("18:40".to_string())
+    );
+    assert_eq!(fields_list[3].place, Some(10));
+
+    // Without a header row, the first column of words is the name and the next the hometown
+    let body = [
+        "  1 Wong, Alice       34 Eugene    18:02",
+        "  2 Smith, Jack       41 Bergman   18:40",
+        "  3 Lopez, Maria      29 Salem     19:15",
+        "  4 Baker, Tom        52 Bend      19:58",
+        "  5 Park, Sue         45 Ashland   20:31",
+        "  6 Bergman, Jack     38 Portland  21:07",
+    ];
+    let fixed_width = body.map(String::from);
+    let ragged = body.map(|line| line.split_whitespace().join(" ")).to_vec();
+    let member_lines = ["Jack,Bergman,Portland".to_string()];
+    let config = Config::default();
+    let prob_of = |result_lines: &[String], name: &str| -> anyhow::Result<f32> {
+        let (_, line_people_list) =
+            config.find_line_people(&member_lines, result_lines, IncludeCity::No)?;
+        Ok(line_people_list
+            .iter()
+            .find(|line_people| line_people.line.contains(name))
+            .map_or(0.0, |line_people| line_people.max_prob))
+    };
+    assert!(prob_of(&fixed_width, "Bergman, Jack")? > 0.99);
+    let column_prob = prob_of(&fixed_width, "Smith, Jack")?;
+    let bag_prob = prob_of(&ragged, "Smith, Jack")?;
+    assert!(column_prob < 0.5, "{column_prob}");
+    assert!(bag_prob > 0.99, "{bag_prob}");
+    Ok(())
+}
+
+#[test]
+fn guessed_columns() {
+    // Separate first and last name columns are both name, and the hometown is the next column
+    let fixed_width = [
+        "  1  Deena     Kastor     35  Boulder      2:29:35",
+        "  2  Magdalena Lewy       34  Oakland      2:30:19",
+        "  3  Blake     Russell    32  Pacific      2:32:40",
+        "  4  Zoila     Gomez      28  Alamosa      2:33:53",
+    ]
+    .map(String::from);
+    let layout = Layout::detect(&fixed_width);
+    // Only interned words, such as members' names, are tokens
+    let name = [Token::new("Deena"), Token::new("Kastor")].into();
+    let city = [Token::new("Boulder")].into();
+    let column_tokens = layout
+        .column_tokens(&Lookup::new(), 0, &fixed_width[0])
+        .unwrap();
+    assert_eq!(column_tokens.name, name);
+    assert_eq!(column_tokens.city, city);
+
+    // A hometown right after the name isn't merged into it, since its words aren't names
+    let name_then_city = [
+        "  1  Mary Brown     Salt Lake City      25:01",
+        "  2  Linda Davis    Colorado Springs    25:40",
+        "  3  John Miller    Mammoth Lakes       26:02",
+        "  4  Jane Wilson    Pacific Grove       26:15",
+    ]
+    .map(String::from);
+    let layout = Layout::detect(&name_then_city);
+    let name = [Token::new("Mary"), Token::new("Brown")].into();
+    let city = [Token::new("Salt"), Token::new("Lake"), Token::new("City")].into();
+    let column_tokens = layout
+        .column_tokens(&Lookup::new(), 0, &name_then_city[0])
+        .unwrap();
+    assert_eq!(column_tokens.name, name);
+    assert_eq!(column_tokens.city, city);
+
+    // Single-spaced words that happen to line up aren't columns
+    let single_spaced = [
+        "1 Ann Lee 30 Bend",
+        "2 Bob Kim 41 Kent",
+        "3 Cal Fox 52 Troy",
+        "4 Dee Orr 47 Lodi",
+    ]
+    .map(String::from);
+    let layout = Layout::detect(&single_spaced);
+    assert!(layout
+        .column_tokens(&Lookup::new(), 0, &single_spaced[0])
+        .is_none());
+    assert_eq!(layout.fields(0, &single_spaced[0]).len(), 5);
+}
+
+#[test]
+fn multi_line_records() -> anyhow::Result<()> {
+    // Pasted from a web page, one field per line, with a blank line between sections
+    let mut result_lines = vec!["WOMEN".to_string()];
+    for (place, name, city, time) in [
+        (1, "Deena Kastor", "Mammoth Lakes, CA", "2:29:35"),
+        (2, "Magdalena Lewy Boulet", "Oakland, CA", "2:30:19"),
+        (3, "Blake Russell", "Pacific Grove, CA", "2:32:40"),
+        (4, "Zoila Gomez", "Alamosa, CO", "2:33:53"),
+    ] {
+        result_lines.extend([place.to_string(), name.to_string(), city.to_string()]);
+        result_lines.push(time.to_string());
+    }
+    result_lines.extend(["".to_string(), "MEN".to_string()]);
+    for (place, name, city, time) in [
+        (1, "Ryan Hall", "Mammoth Lakes, CA", "2:09:02"),
+        (2, "Dathan Ritzenhein", "Eugene, OR", "2:11:07"),
+        (3, "Brian Sell", "Rochester Hills, MI", "2:11:40"),
+    ] {
+        result_lines.extend([place.to_string(), name.to_string(), city.to_string()]);
+        result_lines.push(time.to_string());
+    }
+    let member_lines = [
+        "Blake,Russell,Pacific Grove".to_string(),
+        "Ryan,Hall,Mammoth Lakes".to_string(),
+    ];
+
+    let config = Config {
+        lines_per_record: None,
+        ..Config::default()
+    };
+    let (_, line_people_list) =
+        config.find_line_people(&member_lines, &result_lines, IncludeCity::Yes)?;
+    let matched = line_people_list
+        .iter()
+        .filter(|line_people| line_people.max_prob > 0.5)
+        .map(|line_people| {
+            (
+                line_people.line_index,
+                line_people.fields.place,
+                line_people.fields.gender,
+                line_people.fields.time.map(format_time),
+            )
+        })
+        .sorted()
+        .collect_vec();
+    // Reported at the record's first line, with the fields from all of its lines
+    assert_eq!(
+        matched,
+        vec![
+            (
+                9,
+                Some(3),
+                Some(Gender::Female),
+                Some("2:32:40".to_string())
+            ),
+            (19, Some(1), Some(Gender::Male), Some("2:09:02".to_string())),
+        ]
+    );
+
+    // By default each line is one result
+    let config = Config::default();
+    let (_, line_people_list) =
+        config.find_line_people(&member_lines, &result_lines, IncludeCity::Yes)?;
+    assert!(line_people_list
+        .iter()
+        .all(|line_people| line_people.fields.time.is_none()));
+    Ok(())
+}
+
+#[test]
+fn csv_results() -> anyhow::Result<()> {
+    let text = "Place,Bib,Name,Age,City,5K Split,Chip Time\r\n\
+        1,17,\"Bergman, Jack\",38,Portland,17:40,35:07\r\n\
+        2,43,\"Smith, Jack\",41,\"Bergman, OR\",18:02,36:40\r\n\
+        3,44,\"Lee \"\"Speedy\"\" Park\",29,\"Salem\nOR\",18:30,37:15\r\n";
+    let result_lines = crate::csv::to_result_lines(text)?;
+    assert_eq!(
+        result_lines,
+        vec![
+            "Place\tBib\tName\tAge\tCity\t\tChip Time",
+            "1\t17\tBergman, Jack\t38\tPortland\t17:40\t35:07",
+            "2\t43\tSmith, Jack\t41\tBergman, OR\t18:02\t36:40",
+            "3\t44\tLee \"Speedy\" Park\t29\tSalem OR\t18:30\t37:15",
+        ]
+    );
+
+    // The header row gives the fields and restricts names to the name column
+    let fields_list = parse_result_fields(&result_lines);
+    assert_eq!(fields_list[2].age, Some(41));
+    assert_eq!(
+        fields_list[2].time.map(format_time),
+        Some("36:40".to_string())
+    );
+    let (_, line_people_list) = Config::default().find_line_people(
+        &["Jack,Bergman,Portland".to_string()],
+        &result_lines,
+        IncludeCity::Yes,
+    )?;
+    assert_eq!(line_people_list[0].line_index, 1);
+    assert!(line_people_list
+        .iter()
+        .all(|line_people| line_people.line_index != 2 || line_people.max_prob < 0.5));
+
+    assert!(crate::csv::to_result_lines("1,\"Smith, Jack").is_err());
+    Ok(())
+}
+
+#[test]
+fn html_tables() -> anyhow::Result<()> {
+    // A saved page with a navigation table, a footer table, and the results table between
+    let result_lines =
+        crate::read_input_lines("data/sample_results.html", &crate::SheetOptions::default())?;
+    assert_eq!(
+        result_lines,
+        vec![
+            "Place\tBib\tName\tAge\tHometown\tFinish",
+            "1\t1\tDeena Kastor\t35\tMammoth Lakes, CA\t2:29:35",
+            "2\t43\tMagdalena Lewy Boulet\t34\tOakland, CA\t2:30:19",
+            "3\t17\tBlake Russell\t32\tPacific Grove, CA\t2:32:40",
+            "4\t7\tZoila Gomez\t28\tAlamosa, CO\t2:33:53",
+            "Did not finish\t\t\t\t\t",
+            "\t12\tAnne & Marie Example\t30\tProvo, UT\t",
+        ]
+    );
+
+    let fields_list = parse_result_fields(&result_lines);
+    assert_eq!(fields_list[3].age, Some(32));
+    let (_, line_people_list) = Config::default().find_line_people(
+        &["Blake,Russell,Pacific Grove".to_string()],
+        &result_lines,
+        IncludeCity::Yes,
+    )?;
+    assert_eq!(line_people_list[0].line_index, 3);
+
+    // Without a header, the table with the most rows of results
+    let tables = "<table><tr><td>Home</td><td>About</td></tr></table>\
+        <table><tr><td>1<td>Deena Kastor<td>2:29:35\
+        <tr><td>2<td>Blake Russell<td>2:32:40</table>";
+    assert_eq!(
+        crate::html::to_result_lines(tables)?,
+        vec!["1\tDeena Kastor\t2:29:35", "2\tBlake Russell\t2:32:40"]
+    );
+    assert!(crate::html::to_result_lines("<p>No results yet</p>").is_err());
+    Ok(())
+}
+
+#[cfg(feature = "pdf")]
+#[test]
+fn pdf_results() -> anyhow::Result<()> {
+    let result_lines =
+        crate::read_input_lines("data/sample_results.pdf", &crate::SheetOptions::default())?;
+    // Each cell is drawn on its own, and the second page's font maps its codes through ToUnicode
+    assert_eq!(result_lines.len(), 10);
+    assert_eq!(result_lines[0], "2008 Olympic Trials - Women");
+    assert_eq!(
+        result_lines[2],
+        "1       1      Deena Kastor                     35     Mammoth Lakes, CA      2:29:35"
+    );
+    assert_eq!(
+        result_lines[3],
+        "2       43     Magdalena Lewy Boulet            34     Oakland, CA            2:30:19"
+    );
+    assert_eq!(
+        result_lines[8],
+        "5       12     Anne Example                     30                            2:35:01"
+    );
+
+    let fields_list = parse_result_fields(&result_lines);
+    assert_eq!(fields_list[2].age, Some(35));
+    assert_eq!(
+        fields_list[8].time.map(format_time),
+        Some("2:35:01".to_string())
+    );
+    let (_, line_people_list) = Config::default().find_line_people(
+        &["Zoila,Gomez,Alamosa".to_string()],
+        &result_lines,
+        IncludeCity::Yes,
+    )?;
+    assert_eq!(line_people_list[0].line_index, 7);
+
+    assert!(crate::pdf::to_result_lines(b"Place,Name").is_err());
+    Ok(())
+}
+
+#[test]
+fn encodings() -> anyhow::Result<()> {
+    use crate::encoding::{decode, Encoding};
+
+    // "Zoë Müller, Señora" as Windows-1252, with a curly quote from 0x80-0x9F
+    let windows_1252 = b"Zo\xEB M\xFCller\tSe\xF1ora \x93Z\x94";
+    assert_eq!(
+        decode(windows_1252),
+        (
+            "Zoë Müller\tSeñora \u{201C}Z\u{201D}".to_string(),
+            Encoding::Windows1252
+        )
+    );
+    let utf16_le = [0xFF, 0xFE, b'Z', 0, b'o', 0, 0xEB, 0, b'\n', 0];
+    assert_eq!(decode(&utf16_le), ("Zoë\n".to_string(), Encoding::Utf16Le));
+    // UTF-16 without a byte order mark is recognized by its zero bytes
+    let utf16_be = "Zoë Müller"
+        .encode_utf16()
+        .flat_map(u16::to_be_bytes)
+        .collect_vec();
+    assert_eq!(
+        decode(&utf16_be),
+        ("Zoë Müller".to_string(), Encoding::Utf16Be)
+    );
+    let utf8_bom = "\u{FEFF}Zoë".as_bytes();
+    assert_eq!(decode(utf8_bom), ("Zoë".to_string(), Encoding::Utf8));
+
+    // A Windows-1252 roster no longer stops the run, and the encoding is reported
+    let dir = std::env::temp_dir().join("race_results_encodings");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(
+        dir.join("members.txt"),
+        b"Deena\tKastor\tMammoth Lakes\r\nRen\xE9e\tM\xFCller\tSeattle\r\n",
+    )?;
+    let (member_lines, encoding) =
+        crate::read_input(dir.join("members.txt"), &crate::SheetOptions::default())?;
+    assert_eq!(encoding, Some(Encoding::Windows1252));
+    assert_eq!(member_lines[1], "Renée\tMüller\tSeattle");
+    // Accents are dropped on both sides, so a result without them still matches
+    let result_lines = SAMPLE_RESULTS_STR
+        .lines()
+        .chain(["99\t800\tRenee Muller\t40\tSeattle, WA\t3:40:00"])
+        .map(String::from)
+        .collect_vec();
+    let (_, line_people_list) =
+        Config::default().find_line_people(&member_lines, &result_lines, IncludeCity::Yes)?;
+    assert!(line_people_list.iter().any(|line_people| {
+        line_people.line_index == result_lines.len() - 1 && line_people.max_prob > 0.5
+    }));
+    Ok(())
+}
+
+#[test]
+fn archives() -> anyhow::Result<()> {
+    // A gzipped results file is read by the extension under ".gz"
+    let dir = std::env::temp_dir().join("race_results_archives");
+    std::fs::create_dir_all(&dir)?;
+    let mut encoder = libflate::gzip::Encoder::new(File::create(dir.join("results.txt.gz"))?)?;
+    encoder.write_all(SAMPLE_RESULTS_STR.as_bytes())?;
+    encoder.finish().into_result()?;
+    let result_lines =
+        crate::read_input_lines(dir.join("results.txt.gz"), &crate::SheetOptions::default())?;
+    assert_eq!(result_lines, SAMPLE_RESULTS_STR.lines().collect_vec());
+
+    // Every results file in a zip is a race, named by its entry, skipping directories and metadata
+    let race_list = season::load_races(
+        std::path::Path::new("data/sample_races.zip"),
+        &crate::SheetOptions::default(),
+    )?;
+    assert_eq!(
+        race_list
+            .iter()
+            .map(|race| race.name.as_str())
+            .collect_vec(),
+        ["2008/olympic_trials.txt", "2023/spring.csv.gz"]
+    );
+    assert_eq!(
+        race_list[1].result_lines[1],
+        "1\t400\tAllen, Rick\t52\tSammamish\t3:01:00"
+    );
+
+    let member_lines = ["Rick\tAllen\tSammamish", "Deena\tKastor\tMammoth Lakes"]
+        .map(String::from)
+        .to_vec();
+    let season_report = season::find_season_matches(
+        &Config::default(),
+        &member_lines,
+        &race_list,
+        IncludeCity::Yes,
+        0.5,
+    )?;
+    let deena = season_report
+        .members
+        .iter()
+        .find(|season| season.member_number == 2)
+        .unwrap();
+    assert_eq!(deena.race_count, 2);
+    Ok(())
+}
+
+#[test]
+fn streaming() -> anyhow::Result<()> {
+    let member_lines = SAMPLE_MEMBERS_STR
+        .lines()
+        .chain([
+            "Deena\tKastor\tMammoth Lakes",
+            "Blake\tRussell\tPacific Grove",
+        ])
+        .map(String::from)
+        .collect_vec();
+    let config = Config::default();
+    let member_index = config.index_members(&member_lines)?;
+    let (_, line_people_list) =
+        config.find_line_people_in(&member_index, &result_lines(), IncludeCity::Yes, None)?;
+    let expected = line_people_list
+        .iter()
+        .map(|line_people| (line_people.line_index + 1, line_people.max_prob))
+        .sorted_by_key(|(line_number, _)| *line_number)
+        .collect_vec();
+    assert!(expected.len() >= 2);
+
+    // In one chunk or many, the matches are the same, in file order
+    for stream_chunk_lines in [10_000, 25, 4] {
+        let config = Config {
+            stream_chunk_lines,
+            ..Config::default()
+        };
+        let mut streamed = Vec::new();
+        let match_count = config.find_matches_streaming(
+            &member_index,
+            || Ok(std::io::Cursor::new(SAMPLE_RESULTS_STR.as_bytes())),
+            IncludeCity::Yes,
+            |line_report| streamed.push((line_report.line_number, line_report.max_prob)),
+        )?;
+        assert_eq!(match_count, streamed.len());
+        assert_eq!(streamed, expected, "chunks of {stream_chunk_lines}");
+    }
+
+    assert!(config
+        .find_matches_streaming(
+            &member_index,
+            || Ok(std::io::Cursor::new(SAMPLE_RESULTS_STR.as_bytes())),
+            IncludeCity::Auto,
+            |_| {},
+        )
+        .is_err());
+
+    // Lines are decoded as they're read, UTF-16 included
+    let utf16_bytes = [0xFF, 0xFE]
+        .into_iter()
+        .chain(
+            "Ren\u{e9}e\r\n\u{2014}\n"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        )
+        .collect_vec();
+    let mut line_iter = crate::encoding::decode_lines(utf16_bytes.as_slice());
+    assert_eq!(
+        line_iter.by_ref().collect::<std::io::Result<Vec<_>>>()?,
+        ["Ren\u{e9}e", "\u{2014}"]
+    );
+    assert_eq!(
+        line_iter.encoding(),
+        Some(crate::encoding::Encoding::Utf16Le)
+    );
+    Ok(())
+}
+
+#[test]
+fn parallel_scoring() -> anyhow::Result<()> {
+    // With or without the "parallel" feature, lines come out as scoring them one at a time does
+    let config = Config::default();
+    let member_index = config.index_members(SAMPLE_MEMBERS_STR.lines())?;
+    let parsed = config.parse_results(&result_lines());
+    let result_lines = parsed.records.line_list.as_slice();
+    let (name_stop_words, city_stop_words, city_to_coincidence) = config.find_stop_words(
+        &config.count_result_tokens(&parsed.results_as_tokens),
+        config.results_count(result_lines.len()),
+        &member_index.name_to_coincidence,
+    );
+    let person_list = member_index.person_list(true);
+    let token_to_person_list =
+        config.index_person_list(person_list, &name_stop_words, &city_stop_words);
+    let bib_to_person_list = Config::index_bibs(person_list);
+    let race_fields = RaceFields::new(&parsed.fields_list, None);
+
+    let summarize = |line_points_list: &[crate::LinePoints]| {
+        line_points_list
+            .iter()
+            .map(|line_points| {
+                let person_list = line_points
+                    .person_points_list
+                    .iter()
+                    .map(|person_points| (person_points.person.id, person_points.all_points.delta))
+                    .collect_vec();
+                (line_points.line_index, person_list)
+            })
+            .collect_vec()
+    };
+    let all_at_once = config.find_matching_people_for_each_result_line(
+        result_lines,
+        &parsed.results_as_tokens,
+        &parsed.column_tokens_list,
+        &token_to_person_list,
+        &bib_to_person_list,
+        &member_index.name_to_coincidence,
+        &city_to_coincidence,
+        &race_fields,
+    );
+    let one_at_a_time = (0..result_lines.len())
+        .filter_map(|line_index| {
+            config.find_matching_people_for_result_line(
+                line_index,
+                &result_lines[line_index],
+                &parsed.results_as_tokens[line_index],
+                parsed.column_tokens_list[line_index].as_ref(),
+                &token_to_person_list,
+                &bib_to_person_list,
+                &member_index.name_to_coincidence,
+                &city_to_coincidence,
+                &race_fields,
+            )
+        })
+        .collect_vec();
+    assert!(all_at_once.len() > 10);
+    assert_eq!(summarize(&all_at_once), summarize(&one_at_a_time));
+    Ok(())
+}
+
+#[test]
+fn interned_tokens() -> anyhow::Result<()> {
+    // Equal canonical strings are the same token, which is just a number
+    let token = Token::new("Renée");
+    assert_eq!(token, Token::new("RENEE"));
+    assert_eq!(token, Token::new_or_error("r.e.n.e.e").unwrap());
+    assert_ne!(token, Token::new("RENE"));
+    assert_eq!(token.to_string(), "RENEE");
+    assert_eq!(format!("{token} {token:?}"), "RENEE \"RENEE\"");
+    assert_eq!(core::mem::size_of::<Token>(), 4);
+
+    // Threads interning the same new strings agree on their tokens
+    let word_list = (0..200)
+        .map(|i| {
+            format!(
+                "INTERNED{}",
+                char::from(b'A' + (i % 26) as u8)
+                    .to_string()
+                    .repeat(i / 26 + 1)
+            )
+        })
+        .collect_vec();
+    let token_lists = std::thread::scope(|scope| {
+        let handle_list = (0..4)
+            .map(|_| scope.spawn(|| word_list.iter().map(|word| Token::new(word)).collect_vec()))
+            .collect_vec();
+        handle_list
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect_vec()
+    });
+    assert!(token_lists.iter().all_equal());
+    assert_eq!(token_lists[0].iter().unique().count(), word_list.len());
+    assert!(token_lists[0]
+        .iter()
+        .zip(word_list.iter())
+        .all(|(token, word)| token.to_string() == *word));
+
+    // Words in results are looked up, not interned, so reading results doesn't grow the table
+    let word = "Uninternedword";
+    let result_lines = [format!("1 {word} Kastor 35 Boulder 2:29:35")];
+    let config = Config::default();
+    config.find_matches(["Deena,Kastor,Boulder"], &result_lines, IncludeCity::Yes)?;
+    assert_eq!(Token::find(word), None);
+    assert_eq!(Token::find("kastor"), Some(Token::new("KASTOR")));
+    assert_eq!(Token::find("12:30"), None);
+    Ok(())
+}
+
+#[test]
+fn annotating_while_interning() {
+    // Annotating a line formats tokens, which takes the table's lock. Matching on several
+    // threads while others intern new words must not deadlock.
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let member_lines = SAMPLE_MEMBERS_STR.lines().map(String::from).collect_vec();
+        let result_lines = result_lines();
+        let config = Config::default();
+        let matching_count = std::sync::atomic::AtomicUsize::new(3);
+        std::thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        let matches = config
+                            .find_matches(&member_lines, &result_lines, IncludeCity::Yes)
+                            .unwrap();
+                        assert!(matches.iter().any(|line| line.contains("<mark")));
+                    }
+                    matching_count.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                });
+            }
+            for thread_index in 0..2 {
+                let matching_count = &matching_count;
+                scope.spawn(move || {
+                    // New words, so that writers keep waiting for the lock
+                    let mut i = 0usize;
+                    while matching_count.load(std::sync::atomic::Ordering::SeqCst) > 0 {
+                        let word = format!("{thread_index}-{i}")
+                            .bytes()
+                            .map(|byte| char::from(b'A' + byte % 26))
+                            .collect::<String>();
+                        Token::new(&format!("CONCURRENT{word}"));
+                        i += 1;
+                    }
+                });
+            }
+        });
+        sender.send(()).unwrap();
+    });
+    receiver
+        .recv_timeout(std::time::Duration::from_secs(120))
+        .expect("matching and interning deadlocked");
+}
+
+#[test]
+fn name_table() -> anyhow::Result<()> {
+    use crate::name_table::NameTable;
+
+    // Every name in the text table is found, with its probability to within rounding
+    let name_table = NameTable::bundled();
+    let text = std::fs::read_to_string("data/name_probability.tsv")?;
+    let mut row_count = 0;
+    for line in text.lines().skip(1) {
+        let (name, prob) = line.split('\t').collect_tuple().unwrap();
+        let prob = prob.parse::<f32>()?;
+        let found = name_table.prob(name).unwrap();
+        assert!(
+            (found / prob - 1.0).abs() < 1e-3,
+            "{name}: {found} vs {prob}"
+        );
+        row_count += 1;
+    }
+    assert_eq!(name_table.len(), row_count);
+    assert_eq!(name_table.iter().count(), row_count);
+    assert!(name_table
+        .iter()
+        .map(|(name, _)| name)
+        .tuple_windows()
+        .all(|(a, b)| a < b));
+
+    // Names before, between, and after the table's aren't found
+    for name in ["", "AAAAAAA", "AARONN", "ZZZZZZZZ", "MARY2"] {
+        assert_eq!(name_table.prob(name), None, "{name}");
+    }
+
+    let name_to_coincidence = TokenToCoincidence::default_names();
+    assert_eq!(name_to_coincidence.prob(&Token::new("Q")), 0.01);
+    assert_eq!(
+        name_to_coincidence.prob(&Token::new("Qwzxv")),
+        name_table.min_prob().min(0.01)
+    );
+
+    // Members' names and nicknames are looked up once, when they're indexed
+    let config = Config::default();
+    let member_index = config.index_members(["Robert,Scott,Bellevue"])?;
+    assert!(member_index.name_to_coincidence.name_table.is_none());
+    for name in ["ROBERT", "BOB", "SCOTT"] {
+        let token = Token::new(name);
+        assert_eq!(
+            member_index.name_to_coincidence.prob(&token),
+            config.name_to_coincidence.prob(&token)
+        );
+    }
+    Ok(())
+}
+
+fn result_lines() -> Vec<String> {
+    SAMPLE_RESULTS_STR.lines().map(String::from).collect()
+}
+
+#[test]
+fn spreadsheets() -> anyhow::Result<()> {
+    let results_sheet = crate::SheetOptions {
+        sheet: Some("Results".to_string()),
+        header_row: None,
+    };
+    let result_lines = crate::read_input_lines("data/sample_results.xlsx", &results_sheet)?;
+    assert_eq!(
+        result_lines,
+        vec![
+            "2008 Olympic Trials & more",
+            "Place\tBib\tName\tAge\tHometown\tFinish",
+            "1\t1\tDeena Kastor\t35\tMammoth Lakes, CA\t2:29:35",
+            "2\t43\tMagdalena Lewy Boulet\t34\tOakland, CA\t2:30:19",
+            "3\t17\tBlake Russell\t32\tPacific Grove, CA\t2:32:40",
+            "4\t7\tZoila Gomez\t28\tAlamosa, CO\t2:33:53",
+        ]
+    );
+    // The first sheet by default, and a header row skips the rows above it
+    let first_sheet =
+        crate::read_input_lines("data/sample_results.xlsx", &crate::SheetOptions::default())?;
+    assert_eq!(first_sheet, vec!["Results are on the next sheet"]);
+    let from_header = crate::read_input_lines(
+        "data/sample_results.xlsx",
+        &crate::SheetOptions {
+            header_row: Some(3),
+            ..results_sheet
+        },
+    )?;
+    assert_eq!(from_header[0], "Place\tBib\tName\tAge\tHometown\tFinish");
+
+    let member_sheet = crate::SheetOptions {
+        sheet: Some("2".to_string()),
+        header_row: None,
+    };
+    let member_lines = crate::read_input_lines("data/sample_members.ods", &member_sheet)?;
+    assert_eq!(
+        member_lines,
+        vec![
+            "First\tLast\tCity",
+            "Deena\tKastor\tMammoth Lakes, CA",
+            "Blake\tRussell\tPacific Grove",
+            "Ryan\tHall\tBig Bear Lake",
+        ]
+    );
+
+    let (_, line_people_list) =
+        Config::default().find_line_people(&member_lines, &result_lines, IncludeCity::Yes)?;
+    let matched = line_people_list
+        .iter()
+        .filter(|line_people| line_people.max_prob > 0.5)
+        .map(|line_people| line_people.line_index)
+        .sorted()
+        .collect_vec();
+    assert_eq!(matched, vec![2, 4]);
+
+    let error = crate::read_input_lines(
+        "data/sample_members.ods",
+        &crate::SheetOptions {
+            sheet: Some("Dues".to_string()),
+            header_row: None,
+        },
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("'Notes', 'Roster'"));
+    Ok(())
+}
+
+// A zip file with each entry stored (not compressed), as `zip` reads it
+fn stored_zip(entry_list: &[(&str, &str)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut directory = Vec::new();
+    for (name, text) in entry_list {
+        let offset = bytes.len() as u32;
+        let size = (text.len() as u32).to_le_bytes();
+        let name_len = (name.len() as u16).to_le_bytes();
+        bytes.extend(0x0403_4b50u32.to_le_bytes());
+        bytes.extend([0; 14]);
+        bytes.extend(size);
+        bytes.extend(size);
+        bytes.extend(name_len);
+        bytes.extend([0; 2]);
+        bytes.extend(name.as_bytes());
+        bytes.extend(text.as_bytes());
+
+        directory.extend(0x0201_4b50u32.to_le_bytes());
+        directory.extend([0; 16]);
+        directory.extend(size);
+        directory.extend(size);
+        directory.extend(name_len);
+        directory.extend([0; 12]);
+        directory.extend(offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+    }
+    let directory_offset = bytes.len() as u32;
+    let entry_count = (entry_list.len() as u16).to_le_bytes();
+    bytes.extend(&directory);
+    bytes.extend(0x0605_4b50u32.to_le_bytes());
+    bytes.extend([0; 4]);
+    bytes.extend(entry_count);
+    bytes.extend(entry_count);
+    bytes.extend((directory.len() as u32).to_le_bytes());
+    bytes.extend(directory_offset.to_le_bytes());
+    bytes.extend([0; 2]);
+    bytes
+}
+
+#[test]
+fn spreadsheet_limits() -> anyhow::Result<()> {
+    let xlsx = |sheet_xml: &str| {
+        stored_zip(&[
+            (
+                "xl/workbook.xml",
+                r#"<workbook><sheets><sheet name="Results" r:id="rId1"/></sheets></workbook>"#,
+            ),
+            (
+                "xl/_rels/workbook.xml.rels",
+                r#"<Relationships><Relationship Id="rId1" Target="worksheets/sheet1.xml"/></Relationships>"#,
+            ),
+            ("xl/worksheets/sheet1.xml", sheet_xml),
+        ])
+    };
+    let options = crate::SheetOptions::default();
+    let lines = crate::spreadsheet::to_lines(
+        &xlsx(
+            r#"<sheetData><row r="2"><c r="B2" t="inlineStr"><is><t>Kastor</t></is></c></row></sheetData>"#,
+        ),
+        &options,
+    )?;
+    assert_eq!(lines, vec!["\tKastor"]);
+
+    // A column past usize and a row past Excel's last are errors, not overflow or billions of rows
+    let error = crate::spreadsheet::to_lines(
+        &xlsx(
+            r#"<sheetData><row r="1"><c r="ZZZZZZZZZZZZZZZZZZZZ1"><v>1</v></c></row></sheetData>"#,
+        ),
+        &options,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("out of range"), "{error}");
+    let error = crate::spreadsheet::to_lines(
+        &xlsx(r#"<sheetData><row r="4000000000"><c><v>1</v></c></row></sheetData>"#),
+        &options,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("last row"), "{error}");
+
+    // A comment on an .ods cell isn't part of the cell
+    let ods = stored_zip(&[(
+        "content.xml",
+        r#"<office:document-content><office:body><office:spreadsheet><table:table table:name="Members"><table:table-row><table:table-cell><office:annotation><dc:creator>Pat</dc:creator><text:p>Paid late</text:p></office:annotation><text:p>Deena</text:p></table:table-cell><table:table-cell><text:p>Kastor</text:p></table:table-cell></table:table-row></table:table></office:spreadsheet></office:body></office:document-content>"#,
+    )]);
+    let lines = crate::spreadsheet::to_lines(&ods, &options)?;
+    assert_eq!(lines, vec!["Deena\tKastor"]);
+    Ok(())
+}
@@ -0,0 +1,114 @@
+// Just enough of the zip format to read spreadsheets (.xlsx and .ods are zip files of XML) and
+// archives of results files: the central directory at the end of the file lists the entries,
+// and each entry is either stored or deflated. Zip64 archives and encryption aren't supported.
+
+use std::io::Read;
+
+use anyhow::Context;
+
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+const DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+
+pub(crate) struct ZipArchive<'a> {
+    bytes: &'a [u8],
+    entry_list: Vec<ZipEntry>,
+}
+
+struct ZipEntry {
+    name: String,
+    method: u16,
+    compressed_size: usize,
+    local_header_offset: usize,
+}
+
+pub(crate) fn is_zip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&LOCAL_HEADER.to_le_bytes())
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, anyhow::Error> {
+    let slice = bytes
+        .get(offset..offset + 2)
+        .context("Zip file is cut short")?;
+    Ok(u16::from_le_bytes([slice[0], slice[1]]))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, anyhow::Error> {
+    let slice = bytes
+        .get(offset..offset + 4)
+        .context("Zip file is cut short")?;
+    Ok(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+}
+
+impl<'a> ZipArchive<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, anyhow::Error> {
+        // The end record is last, possibly followed by a comment of up to 64K
+        let end = (0..bytes.len().saturating_sub(21))
+            .rev()
+            .take(22 + u16::MAX as usize)
+            .find(|offset| u32_at(bytes, *offset).ok() == Some(END_OF_DIRECTORY))
+            .context("Not a zip file (no central directory)")?;
+        let entry_count = u16_at(bytes, end + 10)? as usize;
+        let mut offset = u32_at(bytes, end + 16)? as usize;
+
+        let mut entry_list = Vec::with_capacity(entry_count);
+        for _ in 0..entry_count {
+            if u32_at(bytes, offset)? != DIRECTORY_ENTRY {
+                anyhow::bail!("Zip central directory is damaged");
+            }
+            let name_len = u16_at(bytes, offset + 28)? as usize;
+            let extra_len = u16_at(bytes, offset + 30)? as usize;
+            let comment_len = u16_at(bytes, offset + 32)? as usize;
+            let name = bytes
+                .get(offset + 46..offset + 46 + name_len)
+                .context("Zip file is cut short")?;
+            entry_list.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: u16_at(bytes, offset + 10)?,
+                compressed_size: u32_at(bytes, offset + 20)? as usize,
+                local_header_offset: u32_at(bytes, offset + 42)? as usize,
+            });
+            offset += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(Self { bytes, entry_list })
+    }
+
+    // In the order stored, including directories (which end with '/')
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entry_list.iter().map(|entry| entry.name.as_str())
+    }
+
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, anyhow::Error> {
+        let entry = self
+            .entry_list
+            .iter()
+            .find(|entry| entry.name == name)
+            .with_context(|| format!("Zip file has no '{name}'"))?;
+        let offset = entry.local_header_offset;
+        if u32_at(self.bytes, offset)? != LOCAL_HEADER {
+            anyhow::bail!("Zip entry '{name}' is damaged");
+        }
+        let name_len = u16_at(self.bytes, offset + 26)? as usize;
+        let extra_len = u16_at(self.bytes, offset + 28)? as usize;
+        let start = offset + 30 + name_len + extra_len;
+        let data = self
+            .bytes
+            .get(start..start + entry.compressed_size)
+            .context("Zip file is cut short")?;
+        match entry.method {
+            0 => Ok(data.to_vec()),
+            8 => {
+                let mut decoded = Vec::new();
+                libflate::deflate::Decoder::new(data)
+                    .read_to_end(&mut decoded)
+                    .with_context(|| format!("Unzipping '{name}'"))?;
+                Ok(decoded)
+            }
+            method => anyhow::bail!("Zip entry '{name}' uses unsupported compression {method}"),
+        }
+    }
+
+    pub fn read_string(&self, name: &str) -> Result<String, anyhow::Error> {
+        String::from_utf8(self.read(name)?).with_context(|| format!("'{name}' isn't UTF-8"))
+    }
+}
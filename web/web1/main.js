@@ -1,26 +1,58 @@
-import init, { member_match, sample_members, sample_results } from './pkg/web1.js';
+import init, { member_match, csv_to_results, html_to_results, spreadsheet_to_lines, decode_text, detect_encoding, sample_members, sample_results } from './pkg/web1.js';
+
+function isSpreadsheet(file) {
+    const name = file.name.toLowerCase();
+    return name.endsWith('.xlsx') || name.endsWith('.ods');
+}
+
+// Spreadsheets are read as bytes and converted to tab-separated lines
+function loadSpreadsheet(file, textAreaId) {
+    var reader = new FileReader();
+    reader.onload = function (e) {
+        const bytes = new Uint8Array(e.target.result);
+        document.getElementById(textAreaId).value = spreadsheet_to_lines(bytes, '', 0);
+    }
+    reader.readAsArrayBuffer(file);
+}
+
+// Text files are read as bytes and decoded in wasm, which detects Windows-1252 and UTF-16
+function loadText(file, textAreaId, convert) {
+    var reader = new FileReader();
+    reader.onload = function (e) {
+        const bytes = new Uint8Array(e.target.result);
+        const encoding = detect_encoding(bytes);
+        document.getElementById(textAreaId + 'Encoding').innerText =
+            encoding == 'UTF-8' ? '' : 'Read as ' + encoding;
+        document.getElementById(textAreaId).value = convert(decode_text(bytes));
+    }
+    reader.readAsArrayBuffer(file);
+}
 
 function loadMembersFromFile() {
     var fileInput = document.getElementById('membersFile');
     var file = fileInput.files[0];
-    if (file) {
-        var reader = new FileReader();
-        reader.onload = function (e) {
-            document.getElementById('members').value = e.target.result;
-        }
-        reader.readAsText(file);
+    if (file && isSpreadsheet(file)) {
+        loadSpreadsheet(file, 'members');
+    } else if (file) {
+        loadText(file, 'members', text => text);
     }
 }
 
 function loadResultsFromFile() {
     var fileInput = document.getElementById('resultsFile');
     var file = fileInput.files[0];
-    if (file) {
-        var reader = new FileReader();
-        reader.onload = function (e) {
-            document.getElementById('results').value = e.target.result;
-        }
-        reader.readAsText(file);
+    if (file && isSpreadsheet(file)) {
+        loadSpreadsheet(file, 'results');
+    } else if (file) {
+        const name = file.name.toLowerCase();
+        loadText(file, 'results', function (text) {
+            if (name.endsWith('.csv')) {
+                return csv_to_results(text);
+            } else if (name.endsWith('.html') || name.endsWith('.htm')) {
+                return html_to_results(text);
+            }
+            return text;
+        });
     }
 }
 
@@ -30,11 +62,12 @@ async function callWasmFunction() {
     var results = document.getElementById('results').value;
     const includeCitySelect = document.getElementById('includeCity');
     const includeCityValue = Number(includeCitySelect.value);
+    const linesPerRecord = Number(document.getElementById('quantity').value);
 
     document.getElementById('matches').innerText = "Running ...";
     await sleep(0);
 
-    var wasmOutput = member_match(members, results, includeCityValue);
+    var wasmOutput = member_match(members, results, includeCityValue, linesPerRecord);
     document.getElementById('matches').innerHTML = wasmOutput;
 }
 
@@ -43,27 +76,9 @@ function sleep(ms) {
     return new Promise(resolve => setTimeout(resolve, ms));
 }
 
-function convertSpaces(str, n) {
-    let chars = str.split('');
-    for (let i = 0, count = 0; i < chars.length; i++) {
-        if (chars[i] === '\n' || chars[i] === '\u00B6') {
-            count++;
-            chars[i] = count % n === 0 ? '\n' : '\u00B6';
-        }
-    }
-    return chars.join('');
-}
-
-function handleQuantityChange() {
-    const n = document.getElementById('quantity').value;
-    const textArea = document.getElementById('results');
-    textArea.value = convertSpaces(textArea.value, n);
-}
-
 window.callWasmFunction = callWasmFunction;
 window.loadMembersFromFile = loadMembersFromFile;
 window.loadResultsFromFile = loadResultsFromFile;
-window.handleQuantityChange = handleQuantityChange;
 
 window.addEventListener('DOMContentLoaded', (event) => {
     init().then(() => {
@@ -1,9 +1,17 @@
 extern crate alloc;
-use race_results::{Config, IncludeCity, SAMPLE_MEMBERS_STR, SAMPLE_RESULTS_STR};
+use race_results::{
+    encoding, Config, IncludeCity, SheetOptions, SAMPLE_MEMBERS_STR, SAMPLE_RESULTS_STR,
+};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
-pub fn member_match(members: &str, race_results: &str, include_city: u8) -> JsValue {
+// A `lines_per_record` of 0 detects results split across several lines.
+pub fn member_match(
+    members: &str,
+    race_results: &str,
+    include_city: u8,
+    lines_per_record: usize,
+) -> JsValue {
     let include_city = match IncludeCity::try_from(include_city) {
         Ok(include_city) => include_city,
         Err(panic) => return JsValue::from_str(format!("Error: {:?}", panic).as_str()),
@@ -13,6 +21,7 @@ pub fn member_match(members: &str, race_results: &str, include_city: u8) -> JsVa
     let function_result = Config {
         // threshold_probability: 0.0,
         // override_results_count: Some(1081),
+        lines_per_record: (lines_per_record > 0).then_some(lines_per_record),
         ..Config::default()
     }
     .find_matches(members.lines(), race_results.lines(), include_city);
@@ -25,6 +34,50 @@ pub fn member_match(members: &str, race_results: &str, include_city: u8) -> JsVa
     JsValue::from_str(&s)
 }
 
+// A text file's bytes as text, whatever its encoding (see `detect_encoding`)
+#[wasm_bindgen]
+pub fn decode_text(bytes: &[u8]) -> JsValue {
+    JsValue::from_str(&encoding::decode(bytes).0)
+}
+
+// The encoding of a text file's bytes, such as "UTF-8" or "Windows-1252"
+#[wasm_bindgen]
+pub fn detect_encoding(bytes: &[u8]) -> JsValue {
+    JsValue::from_str(&encoding::detect(bytes).to_string())
+}
+
+// A CSV results file as tab-separated lines, one per record
+#[wasm_bindgen]
+pub fn csv_to_results(csv: &str) -> JsValue {
+    match race_results::csv::to_result_lines(csv) {
+        Ok(line_list) => JsValue::from_str(&line_list.join("\n")),
+        Err(panic) => JsValue::from_str(format!("Error: {:?}", panic).as_str()),
+    }
+}
+
+// A saved results page's table as tab-separated lines
+#[wasm_bindgen]
+pub fn html_to_results(html: &str) -> JsValue {
+    match race_results::html::to_result_lines(html) {
+        Ok(line_list) => JsValue::from_str(&line_list.join("\n")),
+        Err(panic) => JsValue::from_str(format!("Error: {:?}", panic).as_str()),
+    }
+}
+
+// An .xlsx or .ods file's bytes as tab-separated lines. An empty `sheet` means the first sheet,
+// and a `header_row` of 0 keeps every row.
+#[wasm_bindgen]
+pub fn spreadsheet_to_lines(bytes: &[u8], sheet: &str, header_row: usize) -> JsValue {
+    let options = SheetOptions {
+        sheet: (!sheet.is_empty()).then(|| sheet.to_string()),
+        header_row: (header_row > 0).then_some(header_row),
+    };
+    match race_results::spreadsheet::to_lines(bytes, &options) {
+        Ok(line_list) => JsValue::from_str(&line_list.join("\n")),
+        Err(panic) => JsValue::from_str(format!("Error: {:?}", panic).as_str()),
+    }
+}
+
 #[wasm_bindgen]
 pub fn sample_members() -> JsValue {
     JsValue::from_str(&SAMPLE_MEMBERS_STR)